@@ -0,0 +1,182 @@
+//! Aho-Corasick automaton over `char` strings.
+//!
+//! Used for literal alternations (`error|fatal|panic|...`): as a prefilter it
+//! finds the leftmost position where any of the literals occurs in a single
+//! pass, and when the whole pattern is such an alternation it is the matcher.
+//!
+//! Matching is leftmost-first, like the backtracking VM: among the literals
+//! occurring at the leftmost start, the one listed first wins.
+
+/// Index of the root state.
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+struct State {
+    /// Outgoing trie edges, sorted by character.
+    trans: Vec<(char, usize)>,
+    /// Longest proper suffix of this state's string that is also a trie state.
+    fail: usize,
+    /// Length of this state's string.
+    depth: usize,
+    /// Indices of the literals equal to this state's string, ascending.
+    ids: Vec<usize>,
+    /// Nearest state on the failure chain whose string is a literal.
+    dict: Option<usize>,
+    /// Length of the longest literal that is a suffix of this state's string.
+    match_len: Option<usize>,
+}
+
+/// A compiled set of literals.
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    states: Vec<State>,
+    /// Literal lengths, indexed by pattern id.
+    lens: Vec<usize>,
+}
+
+impl AhoCorasick {
+    /// Build the automaton. Literal `i` has pattern id `i`; empty literals
+    /// are allowed but never reported.
+    pub fn new(patterns: &[Vec<char>]) -> AhoCorasick {
+        let mut states = vec![State { trans: Vec::new(), fail: ROOT, depth: 0, ids: Vec::new(), dict: None, match_len: None }];
+        for (id, pat) in patterns.iter().enumerate() {
+            let mut s = ROOT;
+            for &c in pat {
+                s = match states[s].trans.binary_search_by_key(&c, |&(ch, _)| ch) {
+                    Ok(i) => states[s].trans[i].1,
+                    Err(i) => {
+                        let next = states.len();
+                        let depth = states[s].depth + 1;
+                        states.push(State { trans: Vec::new(), fail: ROOT, depth, ids: Vec::new(), dict: None, match_len: None });
+                        states[s].trans.insert(i, (c, next));
+                        next
+                    }
+                };
+            }
+            if s != ROOT {
+                states[s].ids.push(id);
+            }
+        }
+
+        // Breadth-first: a state's failure link is shorter, so it is done first.
+        let mut queue = std::collections::VecDeque::from([ROOT]);
+        while let Some(s) = queue.pop_front() {
+            let own = (!states[s].ids.is_empty()).then_some(states[s].depth);
+            let fail = states[s].fail;
+            states[s].match_len = own.or(states[fail].match_len);
+            if s != ROOT {
+                states[s].dict = if states[fail].ids.is_empty() { states[fail].dict } else { Some(fail) };
+            }
+            for i in 0..states[s].trans.len() {
+                let (c, child) = states[s].trans[i];
+                states[child].fail = if s == ROOT {
+                    ROOT
+                } else {
+                    let mut f = states[s].fail;
+                    loop {
+                        if let Some(t) = goto(&states, f, c) {
+                            break t;
+                        }
+                        if f == ROOT {
+                            break ROOT;
+                        }
+                        f = states[f].fail;
+                    }
+                };
+                queue.push_back(child);
+            }
+        }
+        AhoCorasick { states, lens: patterns.iter().map(|p| p.len()).collect() }
+    }
+
+    /// The automaton state after reading `c` in state `s`.
+    fn next(&self, mut s: usize, c: char) -> usize {
+        loop {
+            if let Some(t) = goto(&self.states, s, c) {
+                return t;
+            }
+            if s == ROOT {
+                return ROOT;
+            }
+            s = self.states[s].fail;
+        }
+    }
+
+    /// The leftmost position at or after `from` where some literal starts.
+    pub fn find_start(&self, hay: &[char], from: usize) -> Option<usize> {
+        let mut s = ROOT;
+        let mut best: Option<usize> = None;
+        for (i, &c) in hay.iter().enumerate().skip(from) {
+            s = self.next(s, c);
+            if let Some(len) = self.states[s].match_len {
+                let start = i + 1 - len;
+                if best.is_none_or(|b| start < b) {
+                    best = Some(start);
+                }
+            }
+            // A literal starting before `best` would still be in progress,
+            // making the current state at least that deep.
+            if let Some(b) = best
+                && i + 1 - self.states[s].depth >= b
+            {
+                break;
+            }
+        }
+        best
+    }
+
+    /// The first-listed literal occurring at `start`: `(pattern id, length)`.
+    pub fn first_at(&self, hay: &[char], start: usize) -> Option<(usize, usize)> {
+        let mut s = ROOT;
+        let mut best: Option<usize> = None;
+        for &c in &hay[start..] {
+            match goto(&self.states, s, c) {
+                Some(t) => s = t,
+                None => break,
+            }
+            if let Some(&id) = self.states[s].ids.first()
+                && best.is_none_or(|b| id < b)
+            {
+                best = Some(id);
+            }
+        }
+        best.map(|id| (id, self.lens[id]))
+    }
+
+    /// Which literals occur anywhere in `hay`, overlapping or not, indexed
+    /// by pattern id.
+    pub fn occurring(&self, hay: &[char]) -> Vec<bool> {
+        let mut found = vec![false; self.lens.len()];
+        // A state's literals (and those on its dictionary chain) only need
+        // reporting the first time it is reached.
+        let mut reported = vec![false; self.states.len()];
+        let mut s = ROOT;
+        for &c in hay {
+            s = self.next(s, c);
+            let mut t = Some(s);
+            while let Some(u) = t
+                && !reported[u]
+            {
+                reported[u] = true;
+                for &id in &self.states[u].ids {
+                    found[id] = true;
+                }
+                t = self.states[u].dict;
+            }
+        }
+        found
+    }
+
+    /// Leftmost-first match at or after `from`: `(start, end)`.
+    pub fn find(&self, hay: &[char], from: usize) -> Option<(usize, usize)> {
+        let start = self.find_start(hay, from)?;
+        let (_, len) = self.first_at(hay, start)?;
+        Some((start, start + len))
+    }
+}
+
+/// The trie edge from `s` on `c`, if any.
+fn goto(states: &[State], s: usize, c: char) -> Option<usize> {
+    let trans = &states[s].trans;
+    trans.binary_search_by_key(&c, |&(ch, _)| ch).ok().map(|i| trans[i].1)
+}
@@ -1,4 +1,4 @@
-/// AST types for the regex engine.
+//! AST types for the regex engine.
 
 /// A single node in the regex AST.
 #[derive(Debug, Clone)]
@@ -24,7 +24,7 @@ pub enum AstNode {
     },
     /// Shorthand class: `\d`, `\w`, `\s` and their negations.
     ShorthandClass(ShorthandKind),
-    /// Anchor: `^`, `$`, `\b`.
+    /// Anchor: `^`, `$`, `\b`, `\A`, `\z`, `\G`.
     Anchor(AnchorKind),
     /// Capturing group `(...)` with a group index.
     Group {
@@ -35,8 +35,23 @@ pub enum AstNode {
     NonCapturingGroup {
         node: Box<AstNode>,
     },
+    /// Atomic group `(?>...)`: once the contents match, backtracking into
+    /// them is not allowed. Possessive quantifiers (`a*+`) parse to this.
+    AtomicGroup {
+        node: Box<AstNode>,
+    },
     /// Backreference `\1`, `\2`, etc.
     Backreference(usize),
+    /// Conditional `(?(1)yes|no)` / `(?(name)yes|no)`: matches `yes` if the
+    /// group has participated in the match so far, otherwise `no` (or nothing).
+    Conditional {
+        group: usize,
+        yes: Box<AstNode>,
+        no: Option<Box<AstNode>>,
+    },
+    /// Subroutine call `(?1)`, `(?&name)`, or whole-pattern recursion `(?R)`
+    /// (group 0): re-runs that group's sub-pattern at the current position.
+    Subroutine(usize),
     /// Lookahead `(?=...)` or `(?!...)`.
     Lookahead {
         node: Box<AstNode>,
@@ -98,6 +113,20 @@ pub enum ShorthandKind {
     NonSpace,
 }
 
+impl ShorthandKind {
+    /// The escape this class is written as: `\d`, `\W`, ...
+    pub fn escape(self) -> &'static str {
+        match self {
+            ShorthandKind::Digit => "\\d",
+            ShorthandKind::NonDigit => "\\D",
+            ShorthandKind::Word => "\\w",
+            ShorthandKind::NonWord => "\\W",
+            ShorthandKind::Space => "\\s",
+            ShorthandKind::NonSpace => "\\S",
+        }
+    }
+}
+
 /// Anchor kind.
 #[derive(Debug, Clone, Copy)]
 pub enum AnchorKind {
@@ -109,4 +138,11 @@ pub enum AnchorKind {
     WordBoundary,
     /// `\B` — non-word boundary.
     NonWordBoundary,
+    /// `\A` — absolute start of the text, regardless of any line mode.
+    StartText,
+    /// `\z` / `\Z` — absolute end of the text, regardless of any line mode.
+    EndText,
+    /// `\G` — the position where the current search started (the end of
+    /// the previous match when iterating).
+    SearchStart,
 }
@@ -0,0 +1,191 @@
+//! Canonical character sets used by compiled character classes.
+//!
+//! Every class is normalized at compile time into a sorted list of merged,
+//! non-overlapping `(lo, hi)` ranges. Membership for ASCII characters is a
+//! single bit test in a 128-bit bitmap; anything else falls back to a binary
+//! search over the ranges.
+
+use crate::ast::{ClassItem, ShorthandKind};
+use std::fmt;
+
+/// A set of characters stored as sorted, merged inclusive ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharSet {
+    ranges: Vec<(char, char)>,
+    /// Bit `c` is set iff ASCII character `c` is in the set.
+    ascii: [u64; 2],
+}
+
+impl CharSet {
+    /// Build a set from arbitrary (possibly overlapping, unsorted) ranges.
+    /// Ranges with `lo > hi` are ignored.
+    pub fn from_ranges(mut ranges: Vec<(char, char)>) -> Self {
+        ranges.retain(|&(lo, hi)| lo <= hi);
+        ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+        for (lo, hi) in ranges {
+            if let Some(last) = merged.last_mut() {
+                // Merge overlapping or directly adjacent ranges.
+                if next_char(last.1).is_none_or(|n| lo <= n) {
+                    if hi > last.1 {
+                        last.1 = hi;
+                    }
+                    continue;
+                }
+            }
+            merged.push((lo, hi));
+        }
+        let mut ascii = [0u64; 2];
+        for &(lo, hi) in &merged {
+            if lo as u32 >= 128 {
+                break;
+            }
+            for c in lo as u32..=(hi as u32).min(127) {
+                ascii[(c / 64) as usize] |= 1 << (c % 64);
+            }
+        }
+        CharSet { ranges: merged, ascii }
+    }
+
+    /// Canonicalize a bracketed class, applying case folding before negation
+    /// so that `(?i:[^a])` excludes both `a` and `A`.
+    pub fn from_class(items: &[ClassItem], negated: bool, case_insensitive: bool) -> Self {
+        let mut ranges = Vec::new();
+        for item in items {
+            match item {
+                ClassItem::Literal(c) => ranges.push((*c, *c)),
+                ClassItem::Range(lo, hi) => ranges.push((*lo, *hi)),
+                ClassItem::Shorthand(kind) => {
+                    ranges.extend_from_slice(CharSet::shorthand(*kind).ranges())
+                }
+            }
+        }
+        let mut set = CharSet::from_ranges(ranges);
+        if case_insensitive {
+            set = set.case_fold();
+        }
+        if negated { set.negate() } else { set }
+    }
+
+    /// The set matched by a shorthand class such as `\d` or `\W`.
+    pub fn shorthand(kind: ShorthandKind) -> Self {
+        let digit = || vec![('0', '9')];
+        let word = || vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        // Same characters as `char::is_ascii_whitespace`.
+        let space = || vec![('\t', '\n'), ('\x0C', '\r'), (' ', ' ')];
+        match kind {
+            ShorthandKind::Digit => CharSet::from_ranges(digit()),
+            ShorthandKind::NonDigit => CharSet::from_ranges(digit()).negate(),
+            ShorthandKind::Word => CharSet::from_ranges(word()),
+            ShorthandKind::NonWord => CharSet::from_ranges(word()).negate(),
+            ShorthandKind::Space => CharSet::from_ranges(space()),
+            ShorthandKind::NonSpace => CharSet::from_ranges(space()).negate(),
+        }
+    }
+
+    /// The sorted, merged ranges of this set.
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    /// Test membership: a bitmap lookup for ASCII, binary search otherwise.
+    #[inline]
+    pub fn contains(&self, ch: char) -> bool {
+        let c = ch as u32;
+        if c < 128 {
+            return self.ascii[(c / 64) as usize] & (1 << (c % 64)) != 0;
+        }
+        self.ranges
+            .binary_search_by(|&(lo, hi)| {
+                if hi < ch {
+                    std::cmp::Ordering::Less
+                } else if lo > ch {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    /// The complement of this set over all Unicode scalar values.
+    pub fn negate(&self) -> Self {
+        let mut out = Vec::with_capacity(self.ranges.len() + 1);
+        let mut next = Some('\0');
+        for &(lo, hi) in &self.ranges {
+            if let Some(start) = next
+                && start < lo
+            {
+                // `lo > start >= '\0'`, so a previous char always exists.
+                out.push((start, prev_char(lo).unwrap()));
+            }
+            next = next_char(hi);
+        }
+        if let Some(start) = next {
+            out.push((start, char::MAX));
+        }
+        CharSet::from_ranges(out)
+    }
+
+    /// Close the set under ASCII case folding (matching `char_eq` in the VM).
+    pub fn case_fold(&self) -> Self {
+        let mut ranges = self.ranges.clone();
+        for &(lo, hi) in &self.ranges {
+            for (from_lo, from_hi, to_lo) in [('a', 'z', 'A'), ('A', 'Z', 'a')] {
+                let lo = lo.max(from_lo);
+                let hi = hi.min(from_hi);
+                if lo <= hi {
+                    let shift = |c: char| char::from(to_lo as u8 + (c as u8 - from_lo as u8));
+                    ranges.push((shift(lo), shift(hi)));
+                }
+            }
+        }
+        CharSet::from_ranges(ranges)
+    }
+}
+
+impl fmt::Display for CharSet {
+    /// Class syntax, `[a-z_]`; a set containing both `\0` and `char::MAX`
+    /// prints as the negation of its complement (`[^\n]`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let negated = self.contains('\0') && self.contains(char::MAX);
+        let shown = if negated { self.negate() } else { self.clone() };
+        f.write_str(if negated { "[^" } else { "[" })?;
+        for &(lo, hi) in &shown.ranges {
+            write_class_char(f, lo)?;
+            if hi != lo {
+                if next_char(lo) != Some(hi) {
+                    f.write_str("-")?;
+                }
+                write_class_char(f, hi)?;
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+/// Write `c` as it would appear inside a class.
+fn write_class_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '\\' | ']' | '[' | '^' | '-' => write!(f, "\\{}", c),
+        _ => write!(f, "{}", c.escape_debug()),
+    }
+}
+
+/// The next Unicode scalar value after `c`, skipping the surrogate gap.
+fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// The previous Unicode scalar value before `c`, skipping the surrogate gap.
+fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{E000}' => Some('\u{D7FF}'),
+        '\0' => None,
+        _ => char::from_u32(c as u32 - 1),
+    }
+}
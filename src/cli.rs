@@ -0,0 +1,23 @@
+//! Command-line subcommands. `main` dispatches here when the first argument
+//! is `--grep`, `--replace` or `--split`; otherwise it runs the original
+//! `<pattern> <input>` search. The dashes keep `regex-engine grep <input>`
+//! a search for "grep".
+
+pub mod grep;
+pub mod json;
+pub mod replace;
+pub mod split;
+
+use std::io::{self, Read};
+
+/// The text to work on: `arg` if given, else all of stdin.
+fn input_text(arg: Option<String>) -> io::Result<String> {
+    match arg {
+        Some(text) => Ok(text),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            Ok(text)
+        }
+    }
+}
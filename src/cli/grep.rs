@@ -0,0 +1,280 @@
+//! `regex-engine --grep`: select lines of files or stdin matching a pattern.
+//!
+//! Exit status follows grep: 0 if a line was selected, 1 if none was, 2 on
+//! errors (bad pattern, unreadable file).
+
+use regex_engine::Regex;
+use regex_engine::vm::MatchResult;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: regex-engine --grep [-cilnorvwx] PATTERN [FILE...]";
+
+#[derive(Default)]
+struct Options {
+    /// `-n`: prefix lines with their number.
+    line_numbers: bool,
+    /// `-c`: print the number of selected lines instead.
+    count: bool,
+    /// `-v`: select lines that don't match.
+    invert: bool,
+    /// `-o`: print each non-empty match on its own line.
+    only_matching: bool,
+    /// `-i`: ignore case.
+    ignore_case: bool,
+    /// `-w`: matches must not touch word characters on either side.
+    word: bool,
+    /// `-x`: matches must cover the whole line.
+    line: bool,
+    /// `-l`: print only the names of files with a selected line.
+    files_with_matches: bool,
+    /// `-r`: search directories recursively.
+    recursive: bool,
+}
+
+/// Run `grep` with the arguments after the subcommand name; returns the
+/// exit status.
+pub fn run(args: &[String]) -> i32 {
+    let (opts, pattern, paths) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(msg) => {
+            eprintln!("regex-engine --grep: {}", msg);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    // Check the pattern on its own first: an unbalanced one like `a)|(b`
+    // would close the wrapper's group and parse as something else.
+    let regex = match Regex::new(&pattern).and_then(|_| Regex::new(&wrap_pattern(&pattern, &opts))) {
+        Ok(regex) => regex,
+        Err(e) => {
+            eprintln!("regex-engine --grep: {}", e);
+            return 2;
+        }
+    };
+
+    // No files means stdin, or the current directory with `-r`.
+    let paths = if paths.is_empty() {
+        vec![if opts.recursive { ".".to_string() } else { "-".to_string() }]
+    } else {
+        paths
+    };
+    let show_names = paths.len() > 1 || opts.recursive;
+    let mut status = 1;
+    let mut out = BufWriter::new(io::stdout().lock());
+    for path in &paths {
+        let inputs = if path == "-" {
+            vec![None]
+        } else {
+            match expand(Path::new(path), opts.recursive) {
+                Ok(files) => files.into_iter().map(Some).collect(),
+                Err(e) => {
+                    eprintln!("regex-engine --grep: {}: {}", path, e);
+                    status = 2;
+                    continue;
+                }
+            }
+        };
+        for input in inputs {
+            let name = match &input {
+                None => "(standard input)".to_string(),
+                Some(file) => file.display().to_string(),
+            };
+            let result = match &input {
+                None => grep_reader(&regex, &opts, io::stdin().lock(), &name, show_names, &mut out),
+                Some(file) => File::open(file)
+                    .and_then(|f| grep_reader(&regex, &opts, BufReader::new(f), &name, show_names, &mut out)),
+            };
+            match result {
+                Ok(selected) => {
+                    if selected > 0 && status == 1 {
+                        status = 0;
+                    }
+                }
+                // The reader went away (`| head`) after output was written:
+                // stop quietly.
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return if status == 1 { 0 } else { status },
+                Err(e) => {
+                    eprintln!("regex-engine --grep: {}: {}", name, e);
+                    status = 2;
+                }
+            }
+        }
+    }
+    match out.flush() {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => 2,
+        _ => status,
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(Options, String, Vec<String>), String> {
+    let mut opts = Options::default();
+    let mut positional = Vec::new();
+    let mut options_done = false;
+    for arg in args {
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            options_done = true;
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'n' => opts.line_numbers = true,
+                'c' => opts.count = true,
+                'v' => opts.invert = true,
+                'o' => opts.only_matching = true,
+                'i' => opts.ignore_case = true,
+                'w' => opts.word = true,
+                'x' => opts.line = true,
+                'l' => opts.files_with_matches = true,
+                'r' => opts.recursive = true,
+                _ => return Err(format!("unknown option -{}", flag)),
+            }
+        }
+    }
+    let mut positional = positional.into_iter();
+    let pattern = positional.next().ok_or("missing PATTERN")?;
+    Ok((opts, pattern, positional.collect()))
+}
+
+/// Build the pattern actually searched for: `-i` wraps it in a
+/// case-insensitive group. `-x` and `-w` are checked on its matches instead
+/// (see `find_matches`), since a wrapper would change what `(?R)` recurses
+/// into.
+fn wrap_pattern(pattern: &str, opts: &Options) -> String {
+    if opts.ignore_case {
+        format!("(?i:{})", pattern)
+    } else {
+        pattern.to_string()
+    }
+}
+
+/// The matches in `line` that `-x` and `-w` allow, leftmost first; with
+/// `first_only`, stop at the first. For `-w` the candidates are the
+/// pattern's leftmost-first match at each start, and one that touches a
+/// word character is passed over for a later start.
+fn find_matches(regex: &Regex, opts: &Options, line: &str, first_only: bool) -> Vec<MatchResult> {
+    if opts.line {
+        return regex.fullmatch(line).into_iter().collect();
+    }
+    if !opts.word {
+        return if first_only { regex.search(line).into_iter().collect() } else { regex.find_iter(line).collect() };
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let word_at = |i: Option<usize>| i.and_then(|i| chars.get(i)).is_some_and(|&c| is_word_char(c));
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(m) = regex.search_at(line, from) {
+        if word_at(m.start.checked_sub(1)) || word_at(Some(m.end)) {
+            from = m.start + 1;
+            continue;
+        }
+        from = if m.end > m.start { m.end } else { m.end + 1 };
+        found.push(m);
+        if first_only {
+            break;
+        }
+    }
+    found
+}
+
+/// A character `\w` matches.
+fn is_word_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// The files to search for a command-line path: the path itself, or with
+/// `-r` every file under a directory, in name order. Symbolic links met
+/// while recursing are skipped.
+fn expand(path: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !recursive {
+        return Err(io::Error::other("Is a directory"));
+    }
+    let mut files = Vec::new();
+    let mut entries: Vec<_> = std::fs::read_dir(path)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let kind = entry.file_type()?;
+        if kind.is_dir() {
+            files.extend(expand(&entry.path(), true)?);
+        } else if kind.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Search one input line by line and write the output for it; returns the
+/// number of selected lines (stopping at the first with `-l`). Lines and
+/// counts are prefixed with `name` only with `show_name`; `-l` prints it
+/// either way.
+fn grep_reader(
+    regex: &Regex,
+    opts: &Options,
+    mut reader: impl BufRead,
+    name: &str,
+    show_name: bool,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut selected = 0;
+    let mut line_no = 0;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_no += 1;
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+        }
+        let line = String::from_utf8_lossy(&buf);
+        if find_matches(regex, opts, &line, true).is_empty() != opts.invert {
+            continue;
+        }
+        selected += 1;
+        if opts.files_with_matches {
+            break;
+        }
+        if opts.count {
+            continue;
+        }
+        let mut prefix = String::new();
+        if show_name {
+            prefix.push_str(name);
+            prefix.push(':');
+        }
+        if opts.line_numbers {
+            prefix.push_str(&format!("{}:", line_no));
+        }
+        if !opts.only_matching {
+            writeln!(out, "{}{}", prefix, line)?;
+        } else if !opts.invert {
+            // Inverted lines have no matches to print.
+            let chars: Vec<char> = line.chars().collect();
+            for m in find_matches(regex, opts, &line, false).into_iter().filter(|m| m.end > m.start) {
+                let text: String = chars[m.start..m.end].iter().collect();
+                writeln!(out, "{}{}", prefix, text)?;
+            }
+        }
+    }
+    if opts.files_with_matches {
+        if selected > 0 {
+            writeln!(out, "{}", name)?;
+        }
+    } else if opts.count {
+        if show_name {
+            writeln!(out, "{}:{}", name, selected)?;
+        } else {
+            writeln!(out, "{}", selected)?;
+        }
+    }
+    Ok(selected)
+}
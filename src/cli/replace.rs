@@ -0,0 +1,78 @@
+//! `regex-engine --replace`: substitute matches, like `Regex::replacen`.
+//!
+//! The text is the INPUT argument, printed back with a newline, or else all
+//! of stdin, printed back as is. Without `--all` or `--count` only the first
+//! match is replaced.
+
+use regex_engine::Regex;
+use std::io::{self, Write};
+
+const USAGE: &str = "Usage: regex-engine --replace PATTERN REPLACEMENT [--all|--count N] [INPUT]";
+
+/// Run `replace` with the arguments after the subcommand name; returns the
+/// exit status (0, or 2 on errors).
+pub fn run(args: &[String]) -> i32 {
+    let (limit, mut positional) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(msg) => {
+            eprintln!("regex-engine --replace: {}", msg);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let regex = match Regex::new(&positional[0]) {
+        Ok(regex) => regex,
+        Err(e) => {
+            eprintln!("regex-engine --replace: {}", e);
+            return 2;
+        }
+    };
+    let input_arg = if positional.len() == 3 { positional.pop() } else { None };
+    let from_arg = input_arg.is_some();
+    let input = match super::input_text(input_arg) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("regex-engine --replace: {}", e);
+            return 2;
+        }
+    };
+    let result = regex.replacen(&input, &positional[1], limit);
+    match result {
+        Ok(output) => {
+            let mut out = io::stdout().lock();
+            let _ = if from_arg { writeln!(out, "{}", output) } else { write!(out, "{}", output) };
+            0
+        }
+        Err(e) => {
+            eprintln!("regex-engine --replace: {}", e);
+            2
+        }
+    }
+}
+
+/// The replacement limit (0 for all) and the 2 or 3 positional arguments.
+fn parse_args(args: &[String]) -> Result<(usize, Vec<String>), String> {
+    let mut limit = 1;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => limit = 0,
+            "--count" => {
+                limit = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--count needs a number")?;
+            }
+            "--" => positional.extend(args.by_ref().cloned()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match positional.len() {
+        0 => Err("missing PATTERN".to_string()),
+        1 => Err("missing REPLACEMENT".to_string()),
+        2 | 3 => Ok((limit, positional)),
+        _ => Err("too many arguments".to_string()),
+    }
+}
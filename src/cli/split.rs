@@ -0,0 +1,44 @@
+//! `regex-engine --split`: print the pieces of the text between matches, one
+//! per line, like `Regex::split`. The text is the INPUT argument or else all
+//! of stdin.
+
+use regex_engine::Regex;
+use std::io::{self, BufWriter, Write};
+
+const USAGE: &str = "Usage: regex-engine --split PATTERN [INPUT]";
+
+/// Run `split` with the arguments after the subcommand name; returns the
+/// exit status (0, or 2 on errors).
+pub fn run(args: &[String]) -> i32 {
+    let args: Vec<String> = match args.split_first() {
+        Some((first, rest)) if first == "--" => rest.to_vec(),
+        _ => args.to_vec(),
+    };
+    if args.is_empty() || args.len() > 2 {
+        eprintln!("regex-engine --split: {}", if args.is_empty() { "missing PATTERN" } else { "too many arguments" });
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let regex = match Regex::new(&args[0]) {
+        Ok(regex) => regex,
+        Err(e) => {
+            eprintln!("regex-engine --split: {}", e);
+            return 2;
+        }
+    };
+    let input = match super::input_text(args.get(1).cloned()) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("regex-engine --split: {}", e);
+            return 2;
+        }
+    };
+    let mut out = BufWriter::new(io::stdout().lock());
+    for piece in regex.split(&input) {
+        if writeln!(out, "{}", piece).is_err() {
+            break;
+        }
+    }
+    let _ = out.flush();
+    0
+}
@@ -1,20 +1,30 @@
-/// Compiler: converts AST into bytecode instructions for the VM.
+//! Compiler: converts AST into bytecode instructions for the VM.
 
+use crate::aho_corasick::AhoCorasick;
 use crate::ast::*;
+use crate::charset::CharSet;
+use crate::literal::{self, Finder, Prefilter};
+use crate::simplify::simplify;
+use std::fmt;
 
 /// VM instruction.
 #[derive(Debug, Clone)]
 pub enum Inst {
     /// Match a specific character.
     Char(char),
+    /// Match a run of literal characters (two or more) at once.
+    Str(Vec<char>),
     /// Match any character (except newline).
     AnyChar,
-    /// Match a character class.
-    CharClass { ranges: Vec<ClassItem>, negated: bool },
+    /// Match a character class, canonicalized at compile time (negation and
+    /// case folding already applied).
+    CharClass(CharSet),
     /// Match a shorthand class (\d, \w, \s, etc.)
     ShorthandClass(ShorthandKind),
     /// Successful match.
     Match,
+    /// End of pattern `id` in a combined program (see `combine`).
+    MatchPattern(usize),
     /// Jump to target instruction.
     Jump(usize),
     /// Try first path, fallback to second (split).
@@ -26,253 +36,1038 @@ pub enum Inst {
     AssertStart,
     /// Assert end of string.
     AssertEnd,
+    /// Assert absolute start of text (`\A`).
+    AssertTextStart,
+    /// Assert absolute end of text (`\z`, `\Z`).
+    AssertTextEnd,
+    /// Assert the position where the search started (`\G`).
+    AssertSearchStart,
     /// Assert word boundary.
     AssertWordBoundary,
     /// Assert non-word boundary.
     AssertNonWordBoundary,
     /// Backreference: match the same text as capture group N.
     Backref(usize),
+    /// Atomic group: run the sub-program from `start` to `end` (exclusive) once,
+    /// keep its first match, and continue at `end` with its backtrack points discarded.
+    Atomic(usize, usize),
+    /// Subroutine call: push a return frame and jump to `target`, the entry of
+    /// `group` (0 = whole pattern), switching to the group's lexical `ci_depth`.
+    Call { group: usize, target: usize, ci_depth: usize },
+    /// End of a called group: if the innermost active call is to this group,
+    /// restore the caller's captures and resume after the `Call`; otherwise
+    /// fall through.
+    Return(usize),
+    /// Conditional on a capture group: continue at the next instruction if
+    /// group N has matched, otherwise jump to the target.
+    GroupCheck(usize, usize),
     /// Positive lookahead: sub-program from `start` to `end` (exclusive).
     LookaheadPositive(usize, usize),
     /// Negative lookahead.
     LookaheadNegative(usize, usize),
-    /// Positive lookbehind.
+    /// Positive lookbehind: sub-program compiled in reverse, run right to
+    /// left from the current position.
     LookbehindPositive(usize, usize),
-    /// Negative lookbehind.
+    /// Negative lookbehind (reversed sub-program).
     LookbehindNegative(usize, usize),
+    /// Positive lookbehind whose body can't be reversed (see
+    /// `is_reversible`): try the forward sub-program at every earlier
+    /// position and require it to end at the current one.
+    LookbehindScanPositive(usize, usize),
+    /// Negative lookbehind whose body can't be reversed.
+    LookbehindScanNegative(usize, usize),
     /// No-op (used as placeholder).
     Nop,
+    /// Start a counted loop: set counter register `slot` to 0.
+    RepeatStart(usize),
+    /// Loop head of a counted repetition. With `n` completed iterations in
+    /// register `slot`: run the body (next instruction) while `n < min`, leave
+    /// to `exit` once `n == max`, otherwise split between the two (greedy
+    /// prefers the body).
+    RepeatCheck { slot: usize, min: usize, max: Option<usize>, greedy: bool, exit: usize },
+    /// End of a counted loop body: increment counter register `slot`.
+    RepeatIncr(usize),
     /// Begin case-insensitive matching.
     CaseInsensitiveOn,
     /// End case-insensitive matching.
     CaseInsensitiveOff,
 }
 
+impl Inst {
+    /// Rewrite every instruction address this instruction refers to. An
+    /// unresolved call target (`usize::MAX`) is left alone.
+    pub fn map_targets(self, map: impl Fn(usize) -> usize) -> Inst {
+        match self {
+            Inst::Jump(t) => Inst::Jump(map(t)),
+            Inst::Split(a, b) => Inst::Split(map(a), map(b)),
+            Inst::GroupCheck(g, no) => Inst::GroupCheck(g, map(no)),
+            Inst::RepeatCheck { slot, min, max, greedy, exit } => {
+                Inst::RepeatCheck { slot, min, max, greedy, exit: map(exit) }
+            }
+            Inst::Call { group, target, ci_depth } if target != usize::MAX => {
+                Inst::Call { group, target: map(target), ci_depth }
+            }
+            Inst::Atomic(s, e) => Inst::Atomic(map(s), map(e)),
+            Inst::LookaheadPositive(s, e) => Inst::LookaheadPositive(map(s), map(e)),
+            Inst::LookaheadNegative(s, e) => Inst::LookaheadNegative(map(s), map(e)),
+            Inst::LookbehindPositive(s, e) => Inst::LookbehindPositive(map(s), map(e)),
+            Inst::LookbehindNegative(s, e) => Inst::LookbehindNegative(map(s), map(e)),
+            Inst::LookbehindScanPositive(s, e) => Inst::LookbehindScanPositive(map(s), map(e)),
+            Inst::LookbehindScanNegative(s, e) => Inst::LookbehindScanNegative(map(s), map(e)),
+            inst => inst,
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    /// One-line assembly form, e.g. `split 3, 7` or `class [a-z]`;
+    /// instruction addresses are printed as plain numbers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inst::Char(c) => write!(f, "char {:?}", c),
+            Inst::Str(text) => write!(f, "str {:?}", text.iter().collect::<String>()),
+            Inst::AnyChar => write!(f, "any"),
+            Inst::CharClass(set) => write!(f, "class {}", set),
+            Inst::ShorthandClass(kind) => write!(f, "class {}", kind.escape()),
+            Inst::Match => write!(f, "match"),
+            Inst::MatchPattern(id) => write!(f, "match pattern {}", id),
+            Inst::Jump(t) => write!(f, "jump {}", t),
+            Inst::Split(a, b) => write!(f, "split {}, {}", a, b),
+            Inst::Save(slot) => write!(f, "save {}", slot),
+            Inst::AssertStart => write!(f, "assert ^"),
+            Inst::AssertEnd => write!(f, "assert $"),
+            Inst::AssertTextStart => write!(f, "assert \\A"),
+            Inst::AssertTextEnd => write!(f, "assert \\z"),
+            Inst::AssertSearchStart => write!(f, "assert \\G"),
+            Inst::AssertWordBoundary => write!(f, "assert \\b"),
+            Inst::AssertNonWordBoundary => write!(f, "assert \\B"),
+            Inst::Backref(group) => write!(f, "backref {}", group),
+            Inst::Atomic(s, e) => write!(f, "atomic {}..{}", s, e),
+            Inst::Call { group, target, ci_depth } => write!(f, "call group {} at {} (ci {})", group, target, ci_depth),
+            Inst::Return(group) => write!(f, "return group {}", group),
+            Inst::GroupCheck(group, t) => write!(f, "group check {} else {}", group, t),
+            Inst::LookaheadPositive(s, e) => write!(f, "lookahead {}..{}", s, e),
+            Inst::LookaheadNegative(s, e) => write!(f, "negative lookahead {}..{}", s, e),
+            Inst::LookbehindPositive(s, e) => write!(f, "lookbehind {}..{}", s, e),
+            Inst::LookbehindNegative(s, e) => write!(f, "negative lookbehind {}..{}", s, e),
+            Inst::LookbehindScanPositive(s, e) => write!(f, "lookbehind scan {}..{}", s, e),
+            Inst::LookbehindScanNegative(s, e) => write!(f, "negative lookbehind scan {}..{}", s, e),
+            Inst::Nop => write!(f, "nop"),
+            Inst::RepeatStart(slot) => write!(f, "repeat start r{}", slot),
+            Inst::RepeatCheck { slot, min, max, greedy, exit } => {
+                let max = max.map_or("inf".to_string(), |m| m.to_string());
+                let mode = if *greedy { "greedy" } else { "lazy" };
+                write!(f, "repeat check r{} {}..{} {} exit {}", slot, min, max, mode, exit)
+            }
+            Inst::RepeatIncr(slot) => write!(f, "repeat incr r{}", slot),
+            Inst::CaseInsensitiveOn => write!(f, "case-insensitive on"),
+            Inst::CaseInsensitiveOff => write!(f, "case-insensitive off"),
+        }
+    }
+}
+
+/// Bounded repetitions with at most this many iterations are unrolled; larger
+/// ones compile to a counted loop so the program size doesn't scale with the count.
+const UNROLL_LIMIT: usize = 8;
+
+/// Largest unrolled repetition, in instructions. Without it nested small
+/// counts multiply: `((a{8}){8}){8}` would unroll to 512 copies of `a`.
+const UNROLL_BUDGET: usize = 64;
+
+/// Maximum number of instructions in a compiled program.
+pub const MAX_PROGRAM_SIZE: usize = 1_000_000;
+
 /// Compiled program.
 pub struct Program {
     pub insts: Vec<Inst>,
     pub n_groups: usize,
-    /// If the pattern must start with a specific literal character, store it here.
-    /// Used by the VM to skip starting positions that can't possibly match.
-    pub first_char: Option<char>,
-    /// Whether the pattern is anchored at the start (^).
+    /// Number of counter registers used by counted loops. They are stored in
+    /// the capture slot array after the group slots, so the undo log covers them.
+    pub n_counters: usize,
+    /// Every match starts with a character from this set (`None` if unknown,
+    /// or if the pattern can match the empty string). Used by the VM to skip
+    /// starting positions that can't possibly match.
+    pub first_set: Option<CharSet>,
+    /// Whether the pattern is anchored at the start (^ or \A).
     pub anchored_start: bool,
+    /// Whether the pattern is anchored at the search start (\G).
+    pub anchored_search: bool,
+    /// Whether every match ends at the end of the input (`$`, `\z` or `\Z`
+    /// at the end of every alternative).
+    pub anchored_end: bool,
+    /// Upper bound on the length of a match, if the pattern has one.
+    pub max_len: Option<usize>,
+    /// How far before a match's start its lookbehinds can read (`None` if
+    /// unbounded). Streaming search keeps this much text buffered.
+    pub lookbehind_reach: Option<usize>,
+    /// For end-anchored patterns: the input must end with one of these.
+    pub end_suffixes: Option<Vec<Vec<char>>>,
+    /// For end-anchored patterns of unbounded length: the reversed program,
+    /// run once from the end to rule out inputs with no match at all.
+    pub reverse_program: Option<Box<Program>>,
+    /// Whether this program matches right to left (see `compile_reverse`).
+    pub reverse: bool,
+    /// Jumps to positions where a literal prefix of the pattern occurs.
+    pub prefilter: Option<Prefilter>,
+    /// A literal every match contains; if it is absent, the search fails early.
+    pub required: Option<Finder>,
+    /// Set when the pattern is an alternation of plain literals: the search
+    /// runs this automaton instead of the VM.
+    pub literal_matcher: Option<AhoCorasick>,
 }
 
 /// Compile an AST into a bytecode program.
-pub fn compile(ast: &AstNode, n_groups: usize) -> Program {
-    let mut insts = Vec::new();
-    emit(&mut insts, ast);
-    insts.push(Inst::Match);
-    let first_char = extract_first_char(&insts);
-    let anchored_start = matches!(insts.first(), Some(Inst::AssertStart));
-    Program { insts, n_groups, first_char, anchored_start }
+///
+/// Fails if the program would exceed `MAX_PROGRAM_SIZE` instructions.
+pub fn compile(ast: &AstNode, n_groups: usize) -> Result<Program, String> {
+    let (insts, n_counters) = emit_program(&simplify(ast.clone()), n_groups, false)?;
+    let first_set = first_set(&insts, 0, 0).and_then(|(set, nullable)| (!nullable).then_some(set));
+    let anchored_start = matches!(insts.first(), Some(Inst::AssertStart | Inst::AssertTextStart));
+    let anchored_search = matches!(insts.first(), Some(Inst::AssertSearchStart));
+    let lits = literal::analyze(ast);
+    let anchored_end = is_end_anchored(ast);
+    let end_suffixes = if anchored_end { lits.suffixes.clone() } else { None };
+    let max_len = max_match_len(ast);
+    let reverse_program = if anchored_end && max_len.is_none() {
+        compile_reverse(ast, n_groups).ok().map(Box::new)
+    } else {
+        None
+    };
+    let (prefilter, required) = build_prefilters(lits);
+    let literal_matcher = literal::literal_alternation(ast)
+        .filter(|lits| lits.len() > 1)
+        .map(|lits| AhoCorasick::new(&lits));
+    Ok(Program {
+        insts,
+        n_groups,
+        n_counters,
+        first_set,
+        anchored_start,
+        anchored_search,
+        anchored_end,
+        max_len,
+        lookbehind_reach: lookbehind_reach(ast),
+        end_suffixes,
+        reverse_program,
+        reverse: false,
+        prefilter,
+        required,
+        literal_matcher,
+    })
 }
 
-/// Extract the first required literal character from the instruction stream, if any.
-fn extract_first_char(insts: &[Inst]) -> Option<char> {
-    match insts.first()? {
-        Inst::Char(ch) => Some(*ch),
-        // If the first instruction is AssertStart, check the next one
-        Inst::AssertStart => {
-            match insts.get(1)? {
-                Inst::Char(ch) => Some(*ch),
-                _ => None,
-            }
-        }
-        _ => None,
+/// Compile an AST into a program that matches right to left: run from a
+/// position, it consumes the input backward and stops where the match starts.
+///
+/// Fails for patterns with backreferences, conditionals, atomic groups or
+/// subroutine calls, whose meaning depends on matching left to right.
+pub fn compile_reverse(ast: &AstNode, n_groups: usize) -> Result<Program, String> {
+    if !is_reversible(ast, &[]) {
+        return Err("pattern cannot be compiled in reverse: backreferences, conditionals, atomic groups and subroutine calls are not supported".to_string());
     }
+    let (insts, n_counters) = emit_program(&simplify(ast.clone()), n_groups, true)?;
+    Ok(Program {
+        insts,
+        n_groups,
+        n_counters,
+        first_set: None,
+        anchored_start: false,
+        anchored_search: false,
+        anchored_end: false,
+        max_len: None,
+        lookbehind_reach: None,
+        end_suffixes: None,
+        reverse_program: None,
+        reverse: true,
+        prefilter: None,
+        required: None,
+        literal_matcher: None,
+    })
 }
 
-fn emit(insts: &mut Vec<Inst>, node: &AstNode) {
-    match node {
-        AstNode::Literal(ch) => {
-            insts.push(Inst::Char(*ch));
-        }
-        AstNode::Dot => {
-            insts.push(Inst::AnyChar);
-        }
-        AstNode::Concat(nodes) => {
-            for n in nodes {
-                emit(insts, n);
+/// Emit the instructions for `ast` (left to right, or right to left when
+/// `reverse`) followed by the final `Match`; returns them with the number
+/// of counter registers used.
+fn emit_program(ast: &AstNode, n_groups: usize, reverse: bool) -> Result<(Vec<Inst>, usize), String> {
+    let mut called = vec![false; n_groups + 1];
+    collect_calls(ast, &mut called);
+    let mut c = Compiler {
+        insts: Vec::new(),
+        ci_depth: 0,
+        reverse,
+        called,
+        group_entries: vec![None; n_groups + 1],
+        call_sites: Vec::new(),
+        first_counter_slot: (n_groups + 1) * 2,
+        n_counters: 0,
+    };
+    c.group_entries[0] = Some((0, 0));
+    c.emit(ast);
+    if c.insts.len() > MAX_PROGRAM_SIZE {
+        return Err(format!(
+            "pattern too large: compiled program exceeds {} instructions",
+            MAX_PROGRAM_SIZE
+        ));
+    }
+    if c.called[0] {
+        c.insts.push(Inst::Return(0));
+    }
+    c.insts.push(Inst::Match);
+    c.resolve_calls();
+    Ok((peephole(c.insts), c.n_counters))
+}
+
+/// Bytecode clean-up after emission: thread jumps through `Jump` chains,
+/// then drop `Nop`s, jumps to the next instruction, and
+/// `CaseInsensitiveOn`/`CaseInsensitiveOff` pairs around nothing.
+fn peephole(mut insts: Vec<Inst>) -> Vec<Inst> {
+    // Follow a chain of jumps to its final destination.
+    let resolve = |insts: &[Inst], mut t: usize| {
+        for _ in 0..insts.len() {
+            match insts.get(t) {
+                Some(Inst::Jump(next)) if *next != t => t = *next,
+                _ => break,
             }
         }
-        AstNode::Alternation(branches) => {
-            // a|b|c compiles to:
-            //   split L1, L2
-            //   L1: <a> jump END
-            //   L2: split L3, L4
-            //   L3: <b> jump END
-            //   L4: <c>
-            //   END:
-            let n = branches.len();
-            if n == 0 {
-                return;
-            }
-            if n == 1 {
-                emit(insts, &branches[0]);
-                return;
-            }
-            let mut fixup_jumps = Vec::new();
-            for i in 0..n - 1 {
-                let split_pc = insts.len();
-                insts.push(Inst::Nop); // placeholder for split
-                let branch_start = insts.len();
-                emit(insts, &branches[i]);
-                let jump_pc = insts.len();
-                insts.push(Inst::Nop); // placeholder for jump to end
-                fixup_jumps.push(jump_pc);
-                let next_branch = insts.len();
-                insts[split_pc] = Inst::Split(branch_start, next_branch);
-            }
-            // Last branch
-            emit(insts, &branches[n - 1]);
-            let end = insts.len();
-            for jpc in fixup_jumps {
-                insts[jpc] = Inst::Jump(end);
+        t
+    };
+    for pc in 0..insts.len() {
+        let threaded = match &insts[pc] {
+            Inst::Jump(t) => Inst::Jump(resolve(&insts, *t)),
+            Inst::Split(a, b) => Inst::Split(resolve(&insts, *a), resolve(&insts, *b)),
+            _ => continue,
+        };
+        insts[pc] = threaded;
+    }
+
+    let mut keep = vec![true; insts.len()];
+    for pc in 0..insts.len() {
+        match &insts[pc] {
+            Inst::Nop => keep[pc] = false,
+            Inst::Jump(t) if *t == pc + 1 => keep[pc] = false,
+            Inst::CaseInsensitiveOn if matches!(insts.get(pc + 1), Some(Inst::CaseInsensitiveOff)) => {
+                keep[pc] = false;
+                keep[pc + 1] = false;
             }
+            _ => {}
         }
-        AstNode::Quantifier { node: sub, kind, greedy } => {
-            emit_quantifier(insts, sub, kind, *greedy);
-        }
-        AstNode::CharClass { ranges, negated } => {
-            insts.push(Inst::CharClass {
-                ranges: ranges.clone(),
-                negated: *negated,
+    }
+    if keep.iter().all(|&k| k) {
+        return insts;
+    }
+
+    // A removed instruction does nothing, so a target pointing at it moves to
+    // the next kept one. `new_pc` has an extra entry for "one past the end".
+    let mut new_pc = Vec::with_capacity(insts.len() + 1);
+    let mut n = 0;
+    for &k in &keep {
+        new_pc.push(n);
+        n += k as usize;
+    }
+    new_pc.push(n);
+    let map = |t: usize| new_pc.get(t).copied().unwrap_or(t);
+    insts
+        .into_iter()
+        .zip(keep)
+        .filter(|&(_, k)| k)
+        .map(|(inst, _)| inst.map_targets(map))
+        .collect()
+}
+
+/// Several programs laid out one after another, each ending in
+/// `MatchPattern(id)` instead of `Match`. Used by `RegexSet` and `Lexer`.
+pub struct MultiProgram {
+    pub insts: Vec<Inst>,
+    /// Where each pattern starts, in priority order.
+    pub entries: Vec<Entry>,
+    /// Capture slots needed by the largest program (groups plus counters).
+    pub n_slots: usize,
+}
+
+/// A pattern's entry point in a `MultiProgram`.
+pub struct Entry {
+    pub id: usize,
+    pub pc: usize,
+    /// The pattern's `Program::first_set`.
+    pub first_set: Option<CharSet>,
+    /// The pattern's `Program::anchored_start`.
+    pub anchored_start: bool,
+}
+
+/// Concatenate `programs`, relocating their jump targets. The final `Match`
+/// of each becomes `MatchPattern(id)`; sub-program `Match`es stay.
+pub fn combine<'a>(programs: impl IntoIterator<Item = (usize, &'a Program)>) -> MultiProgram {
+    let mut multi = MultiProgram { insts: Vec::new(), entries: Vec::new(), n_slots: 2 };
+    for (id, program) in programs {
+        let offset = multi.insts.len();
+        multi.entries.push(Entry {
+            id,
+            pc: offset,
+            first_set: program.first_set.clone(),
+            anchored_start: program.anchored_start,
+        });
+        multi.n_slots = multi.n_slots.max((program.n_groups + 1) * 2 + program.n_counters);
+        let last = program.insts.len() - 1;
+        for (pc, inst) in program.insts.iter().enumerate() {
+            multi.insts.push(match inst {
+                Inst::Match if pc == last => Inst::MatchPattern(id),
+                inst => inst.clone().map_targets(|t| t + offset),
             });
         }
-        AstNode::ShorthandClass(kind) => {
-            insts.push(Inst::ShorthandClass(*kind));
+    }
+    multi
+}
+
+/// Whether `node` can be matched right to left. Lookarounds inside are
+/// compiled on their own (lookaheads always forward), so only what they read
+/// matters: groups to their right haven't captured yet when the reversed
+/// program reaches them. A group that is the target of a call must stay
+/// forward. Atomic groups
+/// (and possessive quantifiers) commit to the first alternative that
+/// matches, which depends on the reading direction: forward `(?>a|ab)$`
+/// can't match "ab", backward it can.
+fn is_reversible(node: &AstNode, called: &[bool]) -> bool {
+    match node {
+        AstNode::Backreference(_)
+        | AstNode::Subroutine(_)
+        | AstNode::Conditional { .. }
+        | AstNode::AtomicGroup { .. } => false,
+        AstNode::Group { index, node } => {
+            !called.get(*index).copied().unwrap_or(false) && is_reversible(node, called)
         }
-        AstNode::Anchor(AnchorKind::Start) => {
-            insts.push(Inst::AssertStart);
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => nodes.iter().all(|n| is_reversible(n, called)),
+        AstNode::NonCapturingGroup { node }
+        | AstNode::CaseInsensitive { node }
+        | AstNode::Quantifier { node, .. } => is_reversible(node, called),
+        AstNode::Lookahead { node, .. } | AstNode::Lookbehind { node, .. } => !reads_groups(node),
+        AstNode::Literal(_)
+        | AstNode::Dot
+        | AstNode::CharClass { .. }
+        | AstNode::ShorthandClass(_)
+        | AstNode::Anchor(_) => true,
+    }
+}
+
+/// Whether `node` depends on what groups have captured: it contains a
+/// backreference, a conditional or a subroutine call.
+fn reads_groups(node: &AstNode) -> bool {
+    match node {
+        AstNode::Backreference(_) | AstNode::Subroutine(_) | AstNode::Conditional { .. } => true,
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => nodes.iter().any(reads_groups),
+        AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::CaseInsensitive { node }
+        | AstNode::Quantifier { node, .. }
+        | AstNode::Lookahead { node, .. }
+        | AstNode::Lookbehind { node, .. } => reads_groups(node),
+        AstNode::Literal(_)
+        | AstNode::Dot
+        | AstNode::CharClass { .. }
+        | AstNode::ShorthandClass(_)
+        | AstNode::Anchor(_) => false,
+    }
+}
+
+/// Run literal analysis and turn the results into search prefilters.
+fn build_prefilters(lits: literal::Literals) -> (Option<Prefilter>, Option<Finder>) {
+    // The longest of the required literal and a single-literal suffix; a
+    // single prefix is already checked by the prefilter itself.
+    let suffix = lits.suffixes.filter(|s| s.len() == 1).and_then(|mut s| s.pop());
+    let required = match (lits.required, suffix) {
+        (Some(r), Some(s)) => Some(if s.len() > r.len() { s } else { r }),
+        (r, s) => r.or(s),
+    };
+    let prefilter = lits.prefixes.map(Prefilter::from_prefixes);
+    let required = required
+        .filter(|r| match &prefilter {
+            Some(Prefilter::Prefix(f)) => r.len() > f.needle().len(),
+            _ => true,
+        })
+        .map(Finder::new);
+    (prefilter, required)
+}
+
+/// The characters a match starting at `start` can begin with, and whether
+/// it can reach `Match` without consuming any. Follows control flow through
+/// `Split`/`Jump`/`Save` and zero-width instructions, entering atomic groups
+/// and stepping over lookarounds. Gives up (`None`) on backreferences and
+/// subroutine calls, whose first characters depend on runtime state.
+fn first_set(insts: &[Inst], start: usize, ci_depth: usize) -> Option<(CharSet, bool)> {
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    let mut nullable = false;
+    let mut visited = std::collections::HashSet::new();
+    let mut stack = vec![(start, ci_depth)];
+    while let Some((pc, ci)) = stack.pop() {
+        if !visited.insert((pc, ci)) {
+            continue;
         }
-        AstNode::Anchor(AnchorKind::End) => {
-            insts.push(Inst::AssertEnd);
+        match &insts[pc] {
+            Inst::Char(c) => push_char(&mut ranges, *c, ci > 0),
+            Inst::Str(text) => push_char(&mut ranges, text[0], ci > 0),
+            Inst::AnyChar => ranges.extend([('\0', '\t'), ('\u{B}', char::MAX)]),
+            Inst::CharClass(set) => ranges.extend_from_slice(set.ranges()),
+            Inst::ShorthandClass(kind) => ranges.extend_from_slice(CharSet::shorthand(*kind).ranges()),
+            Inst::Match | Inst::MatchPattern(_) => nullable = true,
+            Inst::Jump(t) => stack.push((*t, ci)),
+            Inst::Split(a, b) => stack.extend([(*a, ci), (*b, ci)]),
+            Inst::GroupCheck(_, no) => stack.extend([(pc + 1, ci), (*no, ci)]),
+            // A counted loop may run its body or leave; which depends on the
+            // counter, so both are possible.
+            Inst::RepeatCheck { exit, .. } => stack.extend([(pc + 1, ci), (*exit, ci)]),
+            Inst::Save(_)
+            | Inst::Nop
+            | Inst::RepeatStart(_)
+            | Inst::RepeatIncr(_)
+            | Inst::AssertStart
+            | Inst::AssertEnd
+            | Inst::AssertTextStart
+            | Inst::AssertTextEnd
+            | Inst::AssertSearchStart
+            | Inst::AssertWordBoundary
+            | Inst::AssertNonWordBoundary => stack.push((pc + 1, ci)),
+            Inst::CaseInsensitiveOn => stack.push((pc + 1, ci + 1)),
+            Inst::CaseInsensitiveOff => stack.push((pc + 1, ci.saturating_sub(1))),
+            Inst::LookaheadPositive(_, end)
+            | Inst::LookaheadNegative(_, end)
+            | Inst::LookbehindPositive(_, end)
+            | Inst::LookbehindNegative(_, end)
+            | Inst::LookbehindScanPositive(_, end)
+            | Inst::LookbehindScanNegative(_, end) => stack.push((*end, ci)),
+            Inst::Atomic(sub_start, sub_end) => {
+                let (sub, sub_nullable) = first_set(insts, *sub_start, ci)?;
+                ranges.extend_from_slice(sub.ranges());
+                if sub_nullable {
+                    stack.push((*sub_end, ci));
+                }
+            }
+            Inst::Backref(_) | Inst::Call { .. } | Inst::Return(_) => return None,
         }
-        AstNode::Anchor(AnchorKind::WordBoundary) => {
-            insts.push(Inst::AssertWordBoundary);
+    }
+    Some((CharSet::from_ranges(ranges), nullable))
+}
+
+/// Add `c` to a first-character set, with its other case if case-insensitive.
+fn push_char(ranges: &mut Vec<(char, char)>, c: char, case_insensitive: bool) {
+    ranges.push((c, c));
+    if case_insensitive {
+        ranges.push((c.to_ascii_lowercase(), c.to_ascii_lowercase()));
+        ranges.push((c.to_ascii_uppercase(), c.to_ascii_uppercase()));
+    }
+}
+
+/// Whether every match of `node` ends at the end of the input.
+fn is_end_anchored(node: &AstNode) -> bool {
+    match node {
+        AstNode::Anchor(AnchorKind::End | AnchorKind::EndText) => true,
+        AstNode::Concat(nodes) => nodes.last().is_some_and(is_end_anchored),
+        AstNode::Alternation(branches) => branches.iter().all(is_end_anchored),
+        AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::CaseInsensitive { node } => is_end_anchored(node),
+        AstNode::Conditional { yes, no, .. } => {
+            is_end_anchored(yes) && no.as_deref().is_some_and(is_end_anchored)
         }
-        AstNode::Anchor(AnchorKind::NonWordBoundary) => {
-            insts.push(Inst::AssertNonWordBoundary);
+        _ => false,
+    }
+}
+
+/// The longest text `node` can match, or `None` if unbounded or unknown
+/// (backreferences and subroutine calls).
+fn max_match_len(node: &AstNode) -> Option<usize> {
+    match node {
+        AstNode::Literal(_) | AstNode::Dot | AstNode::CharClass { .. } | AstNode::ShorthandClass(_) => Some(1),
+        AstNode::Anchor(_) | AstNode::Lookahead { .. } | AstNode::Lookbehind { .. } => Some(0),
+        AstNode::Concat(nodes) => nodes.iter().try_fold(0usize, |acc, n| acc.checked_add(max_match_len(n)?)),
+        AstNode::Alternation(branches) => branches.iter().map(max_match_len).try_fold(0, |acc, m| Some(acc.max(m?))),
+        AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::CaseInsensitive { node } => max_match_len(node),
+        AstNode::Conditional { yes, no, .. } => {
+            let no = match no {
+                Some(no) => max_match_len(no)?,
+                None => 0,
+            };
+            Some(max_match_len(yes)?.max(no))
         }
-        AstNode::Group { index, node: sub } => {
-            // Save start
-            insts.push(Inst::Save(*index * 2));
-            emit(insts, sub);
-            // Save end
-            insts.push(Inst::Save(*index * 2 + 1));
+        AstNode::Quantifier { node, kind, .. } => {
+            let max = match kind {
+                QuantifierKind::Question => 1,
+                QuantifierKind::Exact(n) | QuantifierKind::Range(_, n) => *n,
+                QuantifierKind::Star | QuantifierKind::Plus | QuantifierKind::AtLeast(_) => {
+                    // Unbounded, unless the body can only match the empty string.
+                    return (max_match_len(node)? == 0).then_some(0);
+                }
+            };
+            max_match_len(node)?.checked_mul(max)
         }
-        AstNode::NonCapturingGroup { node: sub } => {
-            emit(insts, sub);
+        AstNode::Backreference(_) | AstNode::Subroutine(_) => None,
+    }
+}
+
+/// How far before the position it is matched at `node` can read through
+/// lookbehinds: a lookbehind's longest body match, plus the reach of
+/// lookbehinds nested in it. `None` if unbounded.
+fn lookbehind_reach(node: &AstNode) -> Option<usize> {
+    match node {
+        AstNode::Lookbehind { node, .. } => max_match_len(node)?.checked_add(lookbehind_reach(node)?),
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => {
+            nodes.iter().try_fold(0, |acc, n| Some(acc.max(lookbehind_reach(n)?)))
         }
-        AstNode::Backreference(idx) => {
-            insts.push(Inst::Backref(*idx));
+        AstNode::Quantifier { node, .. }
+        | AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::CaseInsensitive { node }
+        | AstNode::Lookahead { node, .. } => lookbehind_reach(node),
+        AstNode::Conditional { yes, no, .. } => {
+            let no = match no {
+                Some(no) => lookbehind_reach(no)?,
+                None => 0,
+            };
+            Some(lookbehind_reach(yes)?.max(no))
         }
-        AstNode::Lookahead { node: sub, positive } => {
-            // Emit sub-program inline, wrap with lookahead marker
-            let sub_start = insts.len() + 1; // after the lookahead instruction
-            // We'll compile the sub-pattern as a separate sub-program
-            // Reserve the lookahead instruction
-            let la_pc = insts.len();
-            insts.push(Inst::Nop);
-            emit(insts, sub);
-            insts.push(Inst::Match); // end of sub-program
-            let sub_end = insts.len();
-            if *positive {
-                insts[la_pc] = Inst::LookaheadPositive(sub_start, sub_end);
-            } else {
-                insts[la_pc] = Inst::LookaheadNegative(sub_start, sub_end);
+        AstNode::Literal(_)
+        | AstNode::Dot
+        | AstNode::CharClass { .. }
+        | AstNode::ShorthandClass(_)
+        | AstNode::Anchor(_)
+        | AstNode::Backreference(_)
+        | AstNode::Subroutine(_) => Some(0),
+    }
+}
+
+/// Mark every group that is the target of a subroutine call.
+fn collect_calls(node: &AstNode, called: &mut [bool]) {
+    match node {
+        AstNode::Subroutine(group) => called[*group] = true,
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => {
+            for n in nodes {
+                collect_calls(n, called);
             }
         }
-        AstNode::Lookbehind { node: sub, positive } => {
-            let lb_pc = insts.len();
-            insts.push(Inst::Nop);
-            let sub_start = insts.len();
-            emit(insts, sub);
-            insts.push(Inst::Match);
-            let sub_end = insts.len();
-            if *positive {
-                insts[lb_pc] = Inst::LookbehindPositive(sub_start, sub_end);
-            } else {
-                insts[lb_pc] = Inst::LookbehindNegative(sub_start, sub_end);
+        AstNode::Quantifier { node, .. }
+        | AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::Lookahead { node, .. }
+        | AstNode::Lookbehind { node, .. }
+        | AstNode::CaseInsensitive { node } => collect_calls(node, called),
+        AstNode::Conditional { yes, no, .. } => {
+            collect_calls(yes, called);
+            if let Some(no) = no {
+                collect_calls(no, called);
             }
         }
-        AstNode::CaseInsensitive { node: sub } => {
-            insts.push(Inst::CaseInsensitiveOn);
-            emit(insts, sub);
-            insts.push(Inst::CaseInsensitiveOff);
-        }
+        AstNode::Literal(_)
+        | AstNode::Dot
+        | AstNode::CharClass { .. }
+        | AstNode::ShorthandClass(_)
+        | AstNode::Anchor(_)
+        | AstNode::Backreference(_) => {}
     }
 }
 
-fn emit_quantifier(insts: &mut Vec<Inst>, sub: &AstNode, kind: &QuantifierKind, greedy: bool) {
-    match kind {
-        QuantifierKind::Star => {
-            // L1: split L2, L3  (greedy: prefer L2)
-            // L2: <sub> jump L1
-            // L3:
-            let l1 = insts.len();
-            insts.push(Inst::Nop); // placeholder
-            let l2 = insts.len();
-            emit(insts, sub);
-            insts.push(Inst::Jump(l1));
-            let l3 = insts.len();
-            if greedy {
-                insts[l1] = Inst::Split(l2, l3);
-            } else {
-                insts[l1] = Inst::Split(l3, l2);
-            }
+/// The size of the counted quantifier `kind` over a sub-expression of
+/// `size` instructions when it is unrolled: the count is at most
+/// `UNROLL_LIMIT` and the copies fit `UNROLL_BUDGET`. `None` means a
+/// counted loop.
+fn unrolled_size(kind: &QuantifierKind, size: usize) -> Option<usize> {
+    let unrolled = match kind {
+        QuantifierKind::Exact(n) if *n <= UNROLL_LIMIT => n.saturating_mul(size),
+        QuantifierKind::AtLeast(n) if *n <= UNROLL_LIMIT => (n + 1).saturating_mul(size).saturating_add(2),
+        QuantifierKind::Range(n, m) if *m <= UNROLL_LIMIT => {
+            n.saturating_mul(size).saturating_add((m - n).saturating_mul(size.saturating_add(1)))
         }
-        QuantifierKind::Plus => {
-            // L1: <sub>
-            //     split L1, L2  (greedy: prefer L1)
-            // L2:
-            let l1 = insts.len();
-            emit(insts, sub);
-            let l2 = insts.len() + 1;
-            if greedy {
-                insts.push(Inst::Split(l1, l2));
-            } else {
-                insts.push(Inst::Split(l2, l1));
-            }
+        _ => return None,
+    };
+    (unrolled <= UNROLL_BUDGET).then_some(unrolled)
+}
+
+/// An upper bound on the number of instructions `Compiler::emit` produces
+/// for `node` (literal runs are counted a character at a time).
+fn emitted_size(node: &AstNode) -> usize {
+    match node {
+        AstNode::Concat(nodes) => nodes.iter().map(emitted_size).fold(0, usize::saturating_add),
+        AstNode::Alternation(nodes) => {
+            let branches = nodes.iter().map(emitted_size).fold(0, usize::saturating_add);
+            branches.saturating_add(2 * nodes.len().saturating_sub(1))
         }
-        QuantifierKind::Question => {
-            // split L1, L2 (greedy: prefer L1)
-            // L1: <sub>
-            // L2:
-            let split_pc = insts.len();
-            insts.push(Inst::Nop);
-            let l1 = insts.len();
-            emit(insts, sub);
-            let l2 = insts.len();
-            if greedy {
-                insts[split_pc] = Inst::Split(l1, l2);
-            } else {
-                insts[split_pc] = Inst::Split(l2, l1);
+        AstNode::Quantifier { node, kind, .. } => {
+            let size = emitted_size(node);
+            match kind {
+                QuantifierKind::Star => size.saturating_add(2),
+                QuantifierKind::Plus | QuantifierKind::Question => size.saturating_add(1),
+                _ => unrolled_size(kind, size).unwrap_or(size.saturating_add(4)),
             }
         }
-        QuantifierKind::Exact(n) => {
-            for _ in 0..*n {
-                emit(insts, sub);
+        AstNode::Group { node, .. } => emitted_size(node).saturating_add(3),
+        AstNode::NonCapturingGroup { node } => emitted_size(node),
+        AstNode::AtomicGroup { node }
+        | AstNode::Lookahead { node, .. }
+        | AstNode::Lookbehind { node, .. }
+        | AstNode::CaseInsensitive { node } => emitted_size(node).saturating_add(2),
+        AstNode::Conditional { yes, no, .. } => {
+            emitted_size(yes).saturating_add(no.as_deref().map_or(0, emitted_size)).saturating_add(2)
+        }
+        AstNode::Literal(_)
+        | AstNode::Dot
+        | AstNode::CharClass { .. }
+        | AstNode::ShorthandClass(_)
+        | AstNode::Anchor(_)
+        | AstNode::Backreference(_)
+        | AstNode::Subroutine(_) => 1,
+    }
+}
+
+/// Emission state: the instruction buffer plus the lexical case-insensitive
+/// depth, which lets classes be case-folded at compile time.
+struct Compiler {
+    insts: Vec<Inst>,
+    ci_depth: usize,
+    /// Emit code that matches right to left (reversed programs and
+    /// lookbehind bodies): concatenations are reversed and groups save
+    /// their end before their start.
+    reverse: bool,
+    /// Groups targeted by a subroutine call; they get a `Return` at their end.
+    called: Vec<bool>,
+    /// Entry point of each group as (pc, lexical ci_depth), recorded the
+    /// first time the group is emitted. Group 0 is the whole pattern.
+    group_entries: Vec<Option<(usize, usize)>>,
+    /// `Call` instructions to patch once every group's entry is known.
+    call_sites: Vec<usize>,
+    /// Slot index of the first counter register.
+    first_counter_slot: usize,
+    /// Counter registers allocated so far.
+    n_counters: usize,
+}
+
+impl Compiler {
+    /// Point every `Call` at its group's entry. Calls may precede the group
+    /// they reference, so this runs after the whole pattern is emitted.
+    fn resolve_calls(&mut self) {
+        for &pc in &self.call_sites {
+            if let Inst::Call { group, target, ci_depth } = &mut self.insts[pc] {
+                // Every referenced group is emitted at least once unless it
+                // sits under a `{0}` quantifier; such a call can never match.
+                match self.group_entries[*group] {
+                    Some((entry, ci)) => {
+                        *target = entry;
+                        *ci_depth = ci;
+                    }
+                    None => *target = usize::MAX,
+                }
             }
         }
-        QuantifierKind::AtLeast(n) => {
-            for _ in 0..*n {
-                emit(insts, sub);
+    }
+
+    fn emit(&mut self, node: &AstNode) {
+        // Stop growing once over the limit; `compile` reports the error.
+        if self.insts.len() > MAX_PROGRAM_SIZE {
+            return;
+        }
+        match node {
+            AstNode::Literal(ch) => {
+                self.insts.push(Inst::Char(*ch));
+            }
+            AstNode::Dot => {
+                self.insts.push(Inst::AnyChar);
+            }
+            AstNode::Concat(nodes) => {
+                let ordered: Vec<&AstNode> =
+                    if self.reverse { nodes.iter().rev().collect() } else { nodes.iter().collect() };
+                let mut i = 0;
+                while i < ordered.len() {
+                    // Runs of literals become one `Str`; its text stays in
+                    // reading order, the VM compares it backward in reverse.
+                    let run: Vec<char> = ordered[i..]
+                        .iter()
+                        .map_while(|n| match n {
+                            AstNode::Literal(c) => Some(*c),
+                            _ => None,
+                        })
+                        .collect();
+                    if run.len() >= 2 {
+                        i += run.len();
+                        let mut text = run;
+                        if self.reverse {
+                            text.reverse();
+                        }
+                        self.insts.push(Inst::Str(text));
+                    } else {
+                        self.emit(ordered[i]);
+                        i += 1;
+                    }
+                }
+            }
+            AstNode::Alternation(branches) => {
+                // a|b|c compiles to:
+                //   split L1, L2
+                //   L1: <a> jump END
+                //   L2: split L3, L4
+                //   L3: <b> jump END
+                //   L4: <c>
+                //   END:
+                let n = branches.len();
+                if n == 0 {
+                    return;
+                }
+                if n == 1 {
+                    self.emit(&branches[0]);
+                    return;
+                }
+                let mut fixup_jumps = Vec::new();
+                for branch in &branches[..n - 1] {
+                    let split_pc = self.insts.len();
+                    self.insts.push(Inst::Nop); // placeholder for split
+                    let branch_start = self.insts.len();
+                    self.emit(branch);
+                    let jump_pc = self.insts.len();
+                    self.insts.push(Inst::Nop); // placeholder for jump to end
+                    fixup_jumps.push(jump_pc);
+                    let next_branch = self.insts.len();
+                    self.insts[split_pc] = Inst::Split(branch_start, next_branch);
+                }
+                // Last branch
+                self.emit(&branches[n - 1]);
+                let end = self.insts.len();
+                for jpc in fixup_jumps {
+                    self.insts[jpc] = Inst::Jump(end);
+                }
+            }
+            AstNode::Quantifier { node: sub, kind, greedy } => {
+                self.emit_quantifier(sub, kind, *greedy);
+            }
+            AstNode::CharClass { ranges, negated } => {
+                let set = CharSet::from_class(ranges, *negated, self.ci_depth > 0);
+                self.insts.push(Inst::CharClass(set));
+            }
+            AstNode::ShorthandClass(kind) => {
+                self.insts.push(Inst::ShorthandClass(*kind));
+            }
+            AstNode::Anchor(AnchorKind::Start) => {
+                self.insts.push(Inst::AssertStart);
+            }
+            AstNode::Anchor(AnchorKind::End) => {
+                self.insts.push(Inst::AssertEnd);
+            }
+            AstNode::Anchor(AnchorKind::WordBoundary) => {
+                self.insts.push(Inst::AssertWordBoundary);
+            }
+            AstNode::Anchor(AnchorKind::NonWordBoundary) => {
+                self.insts.push(Inst::AssertNonWordBoundary);
+            }
+            AstNode::Anchor(AnchorKind::StartText) => {
+                self.insts.push(Inst::AssertTextStart);
+            }
+            AstNode::Anchor(AnchorKind::EndText) => {
+                self.insts.push(Inst::AssertTextEnd);
+            }
+            AstNode::Anchor(AnchorKind::SearchStart) => {
+                self.insts.push(Inst::AssertSearchStart);
+            }
+            AstNode::Group { index, node: sub } => {
+                // Called groups are never inside reversed code (see
+                // `is_reversible`), so entries always point at forward code.
+                if self.group_entries[*index].is_none() && !self.reverse {
+                    self.group_entries[*index] = Some((self.insts.len(), self.ci_depth));
+                }
+                // Save start, body, save end (the other way round in reverse)
+                let (first, last) = if self.reverse { (1, 0) } else { (0, 1) };
+                self.insts.push(Inst::Save(*index * 2 + first));
+                self.emit(sub);
+                self.insts.push(Inst::Save(*index * 2 + last));
+                if self.called[*index] {
+                    self.insts.push(Inst::Return(*index));
+                }
+            }
+            AstNode::Subroutine(group) => {
+                self.call_sites.push(self.insts.len());
+                self.insts.push(Inst::Call { group: *group, target: 0, ci_depth: 0 });
+            }
+            AstNode::NonCapturingGroup { node: sub } => {
+                self.emit(sub);
+            }
+            AstNode::AtomicGroup { node: sub } => {
+                let atomic_pc = self.insts.len();
+                self.insts.push(Inst::Nop);
+                let sub_start = self.insts.len();
+                self.emit(sub);
+                self.insts.push(Inst::Match);
+                let sub_end = self.insts.len();
+                self.insts[atomic_pc] = Inst::Atomic(sub_start, sub_end);
+            }
+            AstNode::Backreference(idx) => {
+                self.insts.push(Inst::Backref(*idx));
+            }
+            AstNode::Conditional { group, yes, no } => {
+                //   group_check N, L1
+                //   <yes> jump END
+                //   L1: <no>
+                //   END:
+                let check_pc = self.insts.len();
+                self.insts.push(Inst::Nop); // placeholder for group_check
+                self.emit(yes);
+                let jump_pc = self.insts.len();
+                self.insts.push(Inst::Nop); // placeholder for jump to end
+                let no_start = self.insts.len();
+                if let Some(no) = no {
+                    self.emit(no);
+                }
+                let end = self.insts.len();
+                self.insts[check_pc] = Inst::GroupCheck(*group, no_start);
+                self.insts[jump_pc] = Inst::Jump(end);
+            }
+            AstNode::Lookahead { node: sub, positive } => {
+                // Emit sub-program inline, wrap with lookahead marker
+                let sub_start = self.insts.len() + 1; // after the lookahead instruction
+                // We'll compile the sub-pattern as a separate sub-program
+                // Reserve the lookahead instruction
+                let la_pc = self.insts.len();
+                self.insts.push(Inst::Nop);
+                // A lookahead always reads forward, even inside reversed code.
+                let reverse = std::mem::replace(&mut self.reverse, false);
+                self.emit(sub);
+                self.reverse = reverse;
+                self.insts.push(Inst::Match); // end of sub-program
+                let sub_end = self.insts.len();
+                if *positive {
+                    self.insts[la_pc] = Inst::LookaheadPositive(sub_start, sub_end);
+                } else {
+                    self.insts[la_pc] = Inst::LookaheadNegative(sub_start, sub_end);
+                }
+            }
+            AstNode::Lookbehind { node: sub, positive } => {
+                let lb_pc = self.insts.len();
+                self.insts.push(Inst::Nop);
+                let sub_start = self.insts.len();
+                // Match the body right to left from the current position when
+                // possible; otherwise fall back to scanning start positions.
+                let reversed = is_reversible(sub, &self.called);
+                let reverse = std::mem::replace(&mut self.reverse, reversed);
+                self.emit(sub);
+                self.reverse = reverse;
+                self.insts.push(Inst::Match);
+                let sub_end = self.insts.len();
+                self.insts[lb_pc] = match (reversed, *positive) {
+                    (true, true) => Inst::LookbehindPositive(sub_start, sub_end),
+                    (true, false) => Inst::LookbehindNegative(sub_start, sub_end),
+                    (false, true) => Inst::LookbehindScanPositive(sub_start, sub_end),
+                    (false, false) => Inst::LookbehindScanNegative(sub_start, sub_end),
+                };
+            }
+            AstNode::CaseInsensitive { node: sub } => {
+                self.insts.push(Inst::CaseInsensitiveOn);
+                self.ci_depth += 1;
+                self.emit(sub);
+                self.ci_depth -= 1;
+                self.insts.push(Inst::CaseInsensitiveOff);
             }
-            // Then star
-            emit_quantifier(insts, sub, &QuantifierKind::Star, greedy);
         }
-        QuantifierKind::Range(n, m) => {
-            // First n required
-            for _ in 0..*n {
-                emit(insts, sub);
+    }
+
+    fn emit_quantifier(&mut self, sub: &AstNode, kind: &QuantifierKind, greedy: bool) {
+        match kind {
+            QuantifierKind::Star => {
+                // L1: split L2, L3  (greedy: prefer L2)
+                // L2: <sub> jump L1
+                // L3:
+                let l1 = self.insts.len();
+                self.insts.push(Inst::Nop); // placeholder
+                let l2 = self.insts.len();
+                self.emit(sub);
+                self.insts.push(Inst::Jump(l1));
+                let l3 = self.insts.len();
+                if greedy {
+                    self.insts[l1] = Inst::Split(l2, l3);
+                } else {
+                    self.insts[l1] = Inst::Split(l3, l2);
+                }
+            }
+            QuantifierKind::Plus => {
+                // L1: <sub>
+                //     split L1, L2  (greedy: prefer L1)
+                // L2:
+                let l1 = self.insts.len();
+                self.emit(sub);
+                let l2 = self.insts.len() + 1;
+                if greedy {
+                    self.insts.push(Inst::Split(l1, l2));
+                } else {
+                    self.insts.push(Inst::Split(l2, l1));
+                }
+            }
+            QuantifierKind::Question => {
+                // split L1, L2 (greedy: prefer L1)
+                // L1: <sub>
+                // L2:
+                let split_pc = self.insts.len();
+                self.insts.push(Inst::Nop);
+                let l1 = self.insts.len();
+                self.emit(sub);
+                let l2 = self.insts.len();
+                if greedy {
+                    self.insts[split_pc] = Inst::Split(l1, l2);
+                } else {
+                    self.insts[split_pc] = Inst::Split(l2, l1);
+                }
             }
-            // Then up to (m - n) optional
-            for _ in 0..(*m - *n) {
-                emit_quantifier(insts, sub, &QuantifierKind::Question, greedy);
+            QuantifierKind::Exact(n) if unrolled_size(kind, emitted_size(sub)).is_none() => {
+                self.emit_counted_loop(sub, *n, Some(*n), greedy);
+            }
+            QuantifierKind::AtLeast(n) if unrolled_size(kind, emitted_size(sub)).is_none() => {
+                self.emit_counted_loop(sub, *n, None, greedy);
+            }
+            QuantifierKind::Range(n, m) if unrolled_size(kind, emitted_size(sub)).is_none() => {
+                self.emit_counted_loop(sub, *n, Some(*m), greedy);
+            }
+            QuantifierKind::Exact(n) => {
+                for _ in 0..*n {
+                    self.emit(sub);
+                }
+            }
+            QuantifierKind::AtLeast(n) => {
+                for _ in 0..*n {
+                    self.emit(sub);
+                }
+                // Then star
+                self.emit_quantifier(sub, &QuantifierKind::Star, greedy);
+            }
+            QuantifierKind::Range(n, m) => {
+                // First n required
+                for _ in 0..*n {
+                    self.emit(sub);
+                }
+                // Then up to (m - n) optional
+                for _ in 0..(*m - *n) {
+                    self.emit_quantifier(sub, &QuantifierKind::Question, greedy);
+                }
             }
         }
     }
+
+    /// Emit `sub{min,max}` as a loop over a counter register:
+    ///
+    /// ```text
+    ///       repeat_start R
+    ///   L1: repeat_check R, min, max, END
+    ///       <sub>
+    ///       repeat_incr R
+    ///       jump L1
+    ///   END:
+    /// ```
+    fn emit_counted_loop(&mut self, sub: &AstNode, min: usize, max: Option<usize>, greedy: bool) {
+        let slot = self.first_counter_slot + self.n_counters;
+        self.n_counters += 1;
+        self.insts.push(Inst::RepeatStart(slot));
+        let l1 = self.insts.len();
+        self.insts.push(Inst::Nop); // placeholder for repeat_check
+        self.emit(sub);
+        self.insts.push(Inst::RepeatIncr(slot));
+        self.insts.push(Inst::Jump(l1));
+        let exit = self.insts.len();
+        self.insts[l1] = Inst::RepeatCheck { slot, min, max, greedy, exit };
+    }
 }
@@ -0,0 +1,109 @@
+//! Human-readable dumps of a parsed pattern and its compiled program, for
+//! debugging (the CLI's `--dump-ast` and `--dump-program`).
+
+use crate::ast::{AnchorKind, AstNode, ClassItem, QuantifierKind};
+use crate::compiler::Program;
+use std::fmt::Write;
+
+/// The AST as an indented tree, one node per line:
+///
+/// ```text
+/// Concat
+///   Group 1
+///     Literal 'a'
+///   Quantifier * greedy
+///     Dot
+/// ```
+pub fn ast(node: &AstNode) -> String {
+    let mut out = String::new();
+    write_node(&mut out, node, 0);
+    out
+}
+
+fn write_node(out: &mut String, node: &AstNode, depth: usize) {
+    let (label, children): (String, Vec<&AstNode>) = match node {
+        AstNode::Literal(c) => (format!("Literal {:?}", c), vec![]),
+        AstNode::Dot => ("Dot".to_string(), vec![]),
+        AstNode::Concat(nodes) => ("Concat".to_string(), nodes.iter().collect()),
+        AstNode::Alternation(nodes) => ("Alternation".to_string(), nodes.iter().collect()),
+        AstNode::Quantifier { node, kind, greedy } => {
+            let kind = match kind {
+                QuantifierKind::Star => "*".to_string(),
+                QuantifierKind::Plus => "+".to_string(),
+                QuantifierKind::Question => "?".to_string(),
+                QuantifierKind::Exact(n) => format!("{{{}}}", n),
+                QuantifierKind::AtLeast(n) => format!("{{{},}}", n),
+                QuantifierKind::Range(n, m) => format!("{{{},{}}}", n, m),
+            };
+            let mode = if *greedy { "greedy" } else { "lazy" };
+            (format!("Quantifier {} {}", kind, mode), vec![node])
+        }
+        AstNode::CharClass { ranges, negated } => {
+            let mut class = String::from(if *negated { "[^" } else { "[" });
+            for item in ranges {
+                match item {
+                    ClassItem::Literal(c) => class.extend(c.escape_debug()),
+                    ClassItem::Range(lo, hi) => {
+                        let _ = write!(class, "{}-{}", lo.escape_debug(), hi.escape_debug());
+                    }
+                    ClassItem::Shorthand(kind) => class.push_str(kind.escape()),
+                }
+            }
+            class.push(']');
+            (format!("CharClass {}", class), vec![])
+        }
+        AstNode::ShorthandClass(kind) => (format!("ShorthandClass {}", kind.escape()), vec![]),
+        AstNode::Anchor(kind) => {
+            let anchor = match kind {
+                AnchorKind::Start => "^",
+                AnchorKind::End => "$",
+                AnchorKind::WordBoundary => "\\b",
+                AnchorKind::NonWordBoundary => "\\B",
+                AnchorKind::StartText => "\\A",
+                AnchorKind::EndText => "\\z",
+                AnchorKind::SearchStart => "\\G",
+            };
+            (format!("Anchor {}", anchor), vec![])
+        }
+        AstNode::Group { index, node } => (format!("Group {}", index), vec![node]),
+        AstNode::NonCapturingGroup { node } => ("NonCapturingGroup".to_string(), vec![node]),
+        AstNode::AtomicGroup { node } => ("AtomicGroup".to_string(), vec![node]),
+        AstNode::CaseInsensitive { node } => ("CaseInsensitive".to_string(), vec![node]),
+        AstNode::Backreference(group) => (format!("Backreference {}", group), vec![]),
+        AstNode::Conditional { group, yes, no } => {
+            // The `no` branch, if any, is the second child.
+            let mut children = vec![yes.as_ref()];
+            children.extend(no.as_deref());
+            (format!("Conditional group {}", group), children)
+        }
+        AstNode::Subroutine(group) => (format!("Subroutine {}", group), vec![]),
+        AstNode::Lookahead { node, positive } => {
+            (if *positive { "Lookahead" } else { "NegativeLookahead" }.to_string(), vec![node])
+        }
+        AstNode::Lookbehind { node, positive } => {
+            (if *positive { "Lookbehind" } else { "NegativeLookbehind" }.to_string(), vec![node])
+        }
+    };
+    let _ = writeln!(out, "{}{}", "  ".repeat(depth), label);
+    for child in children {
+        write_node(out, child, depth + 1);
+    }
+}
+
+/// The program's properties the search uses (`first_set`,
+/// `anchored_start`), then its instructions, one per line after their
+/// address. Jump and split targets are addresses in the same listing.
+pub fn program(program: &Program) -> String {
+    let mut out = String::new();
+    let first_set = match &program.first_set {
+        Some(set) => set.to_string(),
+        None => "none".to_string(),
+    };
+    let _ = writeln!(out, "first_set: {}", first_set);
+    let _ = writeln!(out, "anchored_start: {}", program.anchored_start);
+    let width = program.insts.len().saturating_sub(1).to_string().len();
+    for (pc, inst) in program.insts.iter().enumerate() {
+        let _ = writeln!(out, "{:>width$}  {}", pc, inst, width = width);
+    }
+    out
+}
@@ -0,0 +1,124 @@
+//! Tokenizing with an ordered list of `(kind, pattern)` rules.
+//!
+//! The rules are compiled separately and combined into one program
+//! (`compiler::combine`). At each position every rule runs anchored there
+//! (rules whose first-character set excludes the next character are
+//! skipped), and `Priority` decides between rules matching at once.
+//!
+//! Tokens are never empty: a rule's match there is its first non-empty one
+//! in backtracking order, so `a*?` takes one `a`. Input no rule matches
+//! becomes an error token covering the whole unmatched run. `\G` matches at
+//! the start of the token.
+
+use crate::compiler::{self, MultiProgram};
+use crate::parser::Parser;
+use crate::vm;
+
+/// How to choose between rules matching at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// The longest match wins; among equally long ones, the earliest rule.
+    Longest,
+    /// The earliest rule that matches wins, however short its match.
+    First,
+}
+
+/// A token: a rule's kind (`None` for unmatched input) and its span in chars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<K> {
+    pub kind: Option<K>,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A compiled set of token rules.
+pub struct Lexer<K> {
+    kinds: Vec<K>,
+    multi: MultiProgram,
+    priority: Priority,
+}
+
+impl<K: Clone> Lexer<K> {
+    /// Compile `rules`, in priority order. Errors name the failing rule's
+    /// index: `rule 2: unmatched ')'`.
+    pub fn new<I, S>(rules: I, priority: Priority) -> Result<Lexer<K>, String>
+    where
+        I: IntoIterator<Item = (K, S)>,
+        S: AsRef<str>,
+    {
+        let mut kinds = Vec::new();
+        let mut programs = Vec::new();
+        for (i, (kind, pattern)) in rules.into_iter().enumerate() {
+            let mut parser = Parser::new(pattern.as_ref());
+            let program = parser
+                .parse()
+                .and_then(|ast| compiler::compile(&ast, parser.group_count()))
+                .map_err(|e| format!("rule {}: {}", i, e))?;
+            kinds.push(kind);
+            programs.push(program);
+        }
+        let multi = compiler::combine(programs.iter().enumerate());
+        Ok(Lexer { kinds, multi, priority })
+    }
+
+    /// Iterate over the tokens of `input`.
+    pub fn tokens(&self, input: &str) -> Tokens<'_, K> {
+        Tokens { lexer: self, chars: input.chars().collect(), pos: 0 }
+    }
+
+    /// The winning rule at `pos` and where its token ends.
+    fn token_at(&self, chars: &[char], pos: usize) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        for entry in &self.multi.entries {
+            if entry.anchored_start && pos > 0 {
+                continue;
+            }
+            if let Some(set) = &entry.first_set
+                && !chars.get(pos).is_some_and(|&c| set.contains(c))
+            {
+                continue;
+            }
+            let Some(end) = vm::exec_entry(&self.multi, chars, entry.pc, pos, false) else {
+                continue;
+            };
+            match self.priority {
+                Priority::First => return Some((entry.id, end)),
+                Priority::Longest => {
+                    if best.is_none_or(|(_, e)| end > e) {
+                        best = Some((entry.id, end));
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Iterator returned by `Lexer::tokens`.
+pub struct Tokens<'l, K> {
+    lexer: &'l Lexer<K>,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<K: Clone> Iterator for Tokens<'_, K> {
+    type Item = Token<K>;
+
+    fn next(&mut self) -> Option<Token<K>> {
+        let start = self.pos;
+        if start >= self.chars.len() {
+            return None;
+        }
+        if let Some((id, end)) = self.lexer.token_at(&self.chars, start) {
+            self.pos = end;
+            return Some(Token { kind: Some(self.lexer.kinds[id].clone()), start, end });
+        }
+        // Extend the error token up to the next position where a rule matches.
+        let mut end = start + 1;
+        while end < self.chars.len() && self.lexer.token_at(&self.chars, end).is_none() {
+            end += 1;
+        }
+        self.pos = end;
+        Some(Token { kind: None, start, end })
+    }
+}
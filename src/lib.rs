@@ -0,0 +1,24 @@
+//! Regex engine library: pattern → AST (`parser`) → bytecode (`compiler`)
+//! → backtracking execution (`vm`). `Regex` wraps the pipeline; `RegexSet`
+//! runs many patterns at once on the Pike VM (`pike`), and `Lexer` tokenizes
+//! with a list of rules.
+
+pub mod aho_corasick;
+pub mod ast;
+pub mod charset;
+pub mod compiler;
+pub mod dump;
+pub mod lexer;
+pub mod literal;
+pub mod parser;
+pub mod pike;
+pub mod regex;
+pub mod regex_set;
+mod replace;
+pub mod simplify;
+pub mod stream;
+pub mod vm;
+
+pub use lexer::Lexer;
+pub use regex::{Captures, Match, Regex};
+pub use regex_set::RegexSet;
@@ -0,0 +1,397 @@
+//! Literal analysis and substring prefilters.
+//!
+//! Before running the VM, `vm::search_from` can use literal facts about a
+//! pattern to skip positions that cannot start a match:
+//! - the set of literal prefixes every match begins with (`foo|bar|baz`),
+//! - a literal every match must contain (`\w+@example\.com`).
+//!
+//! Large prefix sets (keyword lists) are searched with an Aho-Corasick
+//! automaton; a pattern that is nothing but a literal alternation is matched
+//! by the automaton alone.
+//!
+//! The analysis works on the AST. Each node yields a finite set of literal
+//! strings, each marked *complete* if it is the whole text the node matched
+//! (so a following node may extend it) or *cut* if the node may match more.
+
+use crate::aho_corasick::AhoCorasick;
+use crate::ast::*;
+use crate::charset::CharSet;
+
+/// Maximum number of literals tracked in one set.
+const MAX_LITERALS: usize = 1024;
+
+/// Prefix sets larger than this are searched with Aho-Corasick.
+const MAX_SMALL_PREFIX_SET: usize = 8;
+
+/// Maximum length of a tracked literal.
+const MAX_LITERAL_LEN: usize = 64;
+
+/// A class with at most this many characters expands into single-char literals.
+const MAX_CLASS_EXPANSION: usize = 8;
+
+/// A literal candidate: its characters and whether it is complete.
+#[derive(Debug, Clone, PartialEq)]
+struct Lit {
+    chars: Vec<char>,
+    complete: bool,
+}
+
+/// Literal facts extracted from a pattern.
+#[derive(Debug, Default)]
+pub struct Literals {
+    /// Every match starts with one of these (non-empty) strings.
+    pub prefixes: Option<Vec<Vec<char>>>,
+    /// Every match ends with one of these (non-empty) strings.
+    pub suffixes: Option<Vec<Vec<char>>>,
+    /// The longest literal every match contains.
+    pub required: Option<Vec<char>>,
+}
+
+/// Analyze `ast` for literal prefixes, suffixes and required substrings.
+pub fn analyze(ast: &AstNode) -> Literals {
+    Literals {
+        prefixes: usable(affixes(ast, false, false)),
+        suffixes: usable(affixes(ast, true, false))
+            .map(|set| set.into_iter().map(|s| s.into_iter().rev().collect()).collect()),
+        required: required(ast, false).filter(|r| !r.is_empty()),
+    }
+}
+
+/// If `ast` is an alternation of plain literal strings (no groups, flags,
+/// classes or assertions), return the strings in priority order.
+pub fn literal_alternation(ast: &AstNode) -> Option<Vec<Vec<char>>> {
+    match ast {
+        AstNode::NonCapturingGroup { node } => literal_alternation(node),
+        AstNode::Alternation(branches) => {
+            let mut out = Vec::new();
+            for b in branches {
+                out.extend(literal_alternation(b)?);
+            }
+            Some(out)
+        }
+        _ => literal_string(ast).map(|s| vec![s]),
+    }
+}
+
+/// The non-empty string a node of plain literals matches.
+fn literal_string(node: &AstNode) -> Option<Vec<char>> {
+    match node {
+        AstNode::Literal(c) => Some(vec![*c]),
+        AstNode::Concat(nodes) if !nodes.is_empty() => nodes
+            .iter()
+            .map(|n| match n {
+                AstNode::Literal(c) => Some(*c),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// A set is only useful as a filter if none of its literals is empty.
+fn usable(set: Option<Vec<Lit>>) -> Option<Vec<Vec<char>>> {
+    let set = set?;
+    if set.is_empty() || set.iter().any(|l| l.chars.is_empty()) {
+        return None;
+    }
+    Some(set.into_iter().map(|l| l.chars).collect())
+}
+
+/// The empty complete literal: what a zero-width node contributes.
+fn empty_set() -> Option<Vec<Lit>> {
+    Some(vec![Lit { chars: Vec::new(), complete: true }])
+}
+
+/// Single characters as complete literals, if the set is small enough.
+fn char_set_lits(set: &CharSet) -> Option<Vec<Lit>> {
+    let mut lits = Vec::new();
+    for &(lo, hi) in set.ranges() {
+        for c in lo..=hi {
+            if lits.len() == MAX_CLASS_EXPANSION {
+                return None;
+            }
+            lits.push(Lit { chars: vec![c], complete: true });
+        }
+    }
+    Some(lits)
+}
+
+/// Mark every literal as cut: the node may match more text after it.
+fn cut(set: Option<Vec<Lit>>) -> Option<Vec<Lit>> {
+    set.map(|lits| lits.into_iter().map(|l| Lit { complete: false, ..l }).collect())
+}
+
+/// Concatenate two literal sets. Only complete literals of `a` are extended.
+fn concat(a: Option<Vec<Lit>>, b: Option<Vec<Lit>>) -> Option<Vec<Lit>> {
+    let a = a?;
+    let Some(b) = b else {
+        // `b` is unknown: whatever `a` had is still a valid (cut) prefix.
+        return cut(Some(a));
+    };
+    let mut out: Vec<Lit> = Vec::new();
+    for x in &a {
+        if !x.complete {
+            out.push(x.clone());
+            continue;
+        }
+        for y in &b {
+            let mut chars = x.chars.clone();
+            chars.extend_from_slice(&y.chars);
+            let mut complete = y.complete;
+            if chars.len() > MAX_LITERAL_LEN {
+                chars.truncate(MAX_LITERAL_LEN);
+                complete = false;
+            }
+            out.push(Lit { chars, complete });
+        }
+    }
+    if out.len() > MAX_LITERALS {
+        // Too many combinations: keep `a` alone, which is still valid.
+        return cut(Some(a));
+    }
+    dedup(&mut out);
+    Some(out)
+}
+
+/// Union of alternatives, in order. Later duplicates are dropped.
+fn union(sets: impl Iterator<Item = Option<Vec<Lit>>>) -> Option<Vec<Lit>> {
+    let mut out = Vec::new();
+    for set in sets {
+        out.extend(set?);
+        if out.len() > MAX_LITERALS {
+            return None;
+        }
+    }
+    dedup(&mut out);
+    Some(out)
+}
+
+fn dedup(lits: &mut Vec<Lit>) {
+    let mut seen: Vec<Lit> = Vec::with_capacity(lits.len());
+    lits.retain(|l| {
+        if seen.contains(l) {
+            false
+        } else {
+            seen.push(l.clone());
+            true
+        }
+    });
+}
+
+/// Literal prefixes of `node` (or suffixes, with characters in reverse
+/// order, when `reverse` is set). `None` means "no useful information".
+fn affixes(node: &AstNode, reverse: bool, ci: bool) -> Option<Vec<Lit>> {
+    match node {
+        AstNode::Literal(c) => {
+            if ci {
+                char_set_lits(&CharSet::from_ranges(vec![(*c, *c)]).case_fold())
+            } else {
+                Some(vec![Lit { chars: vec![*c], complete: true }])
+            }
+        }
+        AstNode::CharClass { ranges, negated } => char_set_lits(&CharSet::from_class(ranges, *negated, ci)),
+        AstNode::Dot | AstNode::ShorthandClass(_) => None,
+        // Zero-width: contributes nothing but does not stop the literal.
+        AstNode::Anchor(_) | AstNode::Lookahead { .. } | AstNode::Lookbehind { .. } => empty_set(),
+        AstNode::Concat(nodes) => {
+            let mut acc = empty_set();
+            let mut step = |n: &AstNode| {
+                acc = concat(acc.take(), affixes(n, reverse, ci));
+            };
+            if reverse {
+                nodes.iter().rev().for_each(&mut step);
+            } else {
+                nodes.iter().for_each(&mut step);
+            }
+            acc
+        }
+        AstNode::Alternation(branches) => union(branches.iter().map(|b| affixes(b, reverse, ci))),
+        AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node } => affixes(node, reverse, ci),
+        AstNode::CaseInsensitive { node } => affixes(node, reverse, true),
+        AstNode::Conditional { yes, no, .. } => {
+            let no = match no {
+                Some(no) => affixes(no, reverse, ci),
+                None => empty_set(),
+            };
+            union([affixes(yes, reverse, ci), no].into_iter())
+        }
+        AstNode::Quantifier { node, kind, .. } => {
+            let (min, max) = match kind {
+                QuantifierKind::Star => (0, None),
+                QuantifierKind::Plus => (1, None),
+                QuantifierKind::Question => (0, Some(1)),
+                QuantifierKind::Exact(n) => (*n, Some(*n)),
+                QuantifierKind::AtLeast(n) => (*n, None),
+                QuantifierKind::Range(n, m) => (*n, Some(*m)),
+            };
+            let sub = affixes(node, reverse, ci);
+            if max == Some(1) && min == 0 {
+                // `x?`: either x or nothing.
+                return union([sub, empty_set()].into_iter());
+            }
+            if min == 0 {
+                return None;
+            }
+            let mut acc = empty_set();
+            for _ in 0..min {
+                acc = concat(acc, sub.clone());
+                if acc.as_ref().is_none_or(|lits| lits.iter().all(|l| !l.complete)) {
+                    break;
+                }
+            }
+            if max == Some(min) { acc } else { cut(acc) }
+        }
+        AstNode::Backreference(_) | AstNode::Subroutine(_) => None,
+    }
+}
+
+/// The longest literal every match of `node` must contain.
+fn required(node: &AstNode, ci: bool) -> Option<Vec<char>> {
+    match node {
+        AstNode::Literal(c) if !ci => Some(vec![*c]),
+        AstNode::Concat(nodes) => {
+            // Runs of single-literal nodes join into longer required strings;
+            // any child's own required literal is also required.
+            let mut best: Option<Vec<char>> = None;
+            let mut run: Vec<char> = Vec::new();
+            let consider = |cand: &[char], best: &mut Option<Vec<char>>| {
+                if best.as_ref().is_none_or(|b| cand.len() > b.len()) {
+                    *best = Some(cand.to_vec());
+                }
+            };
+            for n in nodes {
+                match single_literal(n, ci) {
+                    Some(lit) => run.extend(lit),
+                    None if is_zero_width(n) => {}
+                    None => {
+                        consider(&run, &mut best);
+                        run.clear();
+                        if let Some(r) = required(n, ci) {
+                            consider(&r, &mut best);
+                        }
+                    }
+                }
+            }
+            consider(&run, &mut best);
+            best.filter(|b| !b.is_empty())
+        }
+        AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node } => required(node, ci),
+        AstNode::CaseInsensitive { node } => required(node, true),
+        AstNode::Quantifier { node, kind, .. } => match kind {
+            QuantifierKind::Star | QuantifierKind::Question => None,
+            QuantifierKind::Exact(0) | QuantifierKind::AtLeast(0) | QuantifierKind::Range(0, _) => None,
+            _ => required(node, ci),
+        },
+        _ => None,
+    }
+}
+
+/// If `node` always matches exactly one fixed string, return it.
+fn single_literal(node: &AstNode, ci: bool) -> Option<Vec<char>> {
+    match affixes(node, false, ci)?.as_slice() {
+        [lit] if lit.complete && !lit.chars.is_empty() => Some(lit.chars.clone()),
+        _ => None,
+    }
+}
+
+fn is_zero_width(node: &AstNode) -> bool {
+    matches!(node, AstNode::Anchor(_) | AstNode::Lookahead { .. } | AstNode::Lookbehind { .. })
+}
+
+/// Substring finder over `&[char]`: scans for the needle's rarest character
+/// (by a fixed frequency heuristic) and verifies the full needle around it.
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: Vec<char>,
+    /// Index in `needle` of the character we scan for.
+    rare_idx: usize,
+}
+
+impl Finder {
+    pub fn new(needle: Vec<char>) -> Finder {
+        let rare_idx = (0..needle.len())
+            .min_by_key(|&i| char_frequency_rank(needle[i]))
+            .unwrap_or(0);
+        Finder { needle, rare_idx }
+    }
+
+    pub fn needle(&self) -> &[char] {
+        &self.needle
+    }
+
+    /// Start of the first occurrence of the needle at or after `from`.
+    pub fn find(&self, hay: &[char], from: usize) -> Option<usize> {
+        let n = self.needle.len();
+        if n == 0 {
+            return Some(from);
+        }
+        let rare = self.needle[self.rare_idx];
+        let mut i = from + self.rare_idx;
+        while i + (n - self.rare_idx) <= hay.len() {
+            if hay[i] == rare {
+                let start = i - self.rare_idx;
+                if hay[start..start + n] == self.needle[..] {
+                    return Some(start);
+                }
+            }
+            i += 1;
+        }
+        None
+    }
+}
+
+/// Rough frequency rank of a character in typical text: higher is more
+/// common. Used only to pick which needle character to scan for.
+fn char_frequency_rank(c: char) -> u8 {
+    const COMMON: &str = " etaoinsrhldcumfpgwybvkxjqz";
+    match COMMON.find(c.to_ascii_lowercase()) {
+        Some(i) => 255 - i as u8,
+        None if c.is_ascii_digit() => 200,
+        None if c.is_ascii_punctuation() => 100,
+        None => 0,
+    }
+}
+
+/// Skips positions that cannot start a match.
+#[derive(Debug, Clone)]
+pub enum Prefilter {
+    /// Every match starts with this literal.
+    Prefix(Finder),
+    /// Every match starts with one of a few literals.
+    PrefixSet {
+        lits: Vec<Vec<char>>,
+        /// First characters of `lits`, for a quick rejection test.
+        first: CharSet,
+    },
+    /// Every match starts with one of many literals.
+    AhoCorasick(AhoCorasick),
+}
+
+impl Prefilter {
+    /// Build a prefilter from a set of literal prefixes.
+    pub fn from_prefixes(mut prefixes: Vec<Vec<char>>) -> Prefilter {
+        if prefixes.len() == 1 {
+            return Prefilter::Prefix(Finder::new(prefixes.pop().unwrap()));
+        }
+        if prefixes.len() > MAX_SMALL_PREFIX_SET {
+            return Prefilter::AhoCorasick(AhoCorasick::new(&prefixes));
+        }
+        let first = CharSet::from_ranges(prefixes.iter().map(|l| (l[0], l[0])).collect());
+        Prefilter::PrefixSet { lits: prefixes, first }
+    }
+
+    /// The first position at or after `from` where a match could start.
+    pub fn next_candidate(&self, hay: &[char], from: usize) -> Option<usize> {
+        match self {
+            Prefilter::Prefix(finder) => finder.find(hay, from),
+            Prefilter::PrefixSet { lits, first } => (from..hay.len()).find(|&p| {
+                first.contains(hay[p]) && lits.iter().any(|l| hay[p..].starts_with(l))
+            }),
+            Prefilter::AhoCorasick(ac) => ac.find_start(hay, from),
+        }
+    }
+}
@@ -1,51 +1,146 @@
-mod ast;
-mod compiler;
-mod parser;
-mod vm;
+mod cli;
 
+use regex_engine::compiler::{self, Program};
+use regex_engine::dump;
+use regex_engine::parser;
+use regex_engine::vm::{self, MatchResult};
 use std::env;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: regex-engine <pattern> <input>");
+    let argv: Vec<String> = env::args().skip(1).collect();
+    if let Some(command) = argv.first() {
+        let status = match command.as_str() {
+            "--grep" => Some(cli::grep::run(&argv[1..])),
+            "--replace" => Some(cli::replace::run(&argv[1..])),
+            "--split" => Some(cli::split::run(&argv[1..])),
+            _ => None,
+        };
+        if let Some(status) = status {
+            std::process::exit(status);
+        }
+    }
+
+    let usage = || -> ! {
+        eprintln!("Usage: regex-engine [--match|--fullmatch] [--pos N] [--endpos N] [--format text|json] [--show-unset] [--dump-ast] [--dump-program] [--trace] <pattern> <input>");
+        eprintln!("       regex-engine --grep|--replace|--split ARGS...");
+        std::process::exit(1);
+    };
+    let mut args = argv;
+    // `--match` anchors at the start (`re.match`), `--fullmatch` also at the
+    // end. `--pos`/`--endpos` limit either like the `pos`/`endpos` arguments
+    // of Python's `pattern.search`, `match` and `fullmatch`.
+    // `--format json` reports every match (like `finditer`) as one JSON object.
+    // `--show-unset` prints `GROUP i:<unset>` for groups that didn't take part,
+    // which otherwise look like empty ones. `--dump-ast` and `--dump-program`
+    // print the parsed pattern and the compiled program first; `--trace` logs
+    // each VM step of a plain search to stderr.
+    let mut mode = None;
+    let mut pos = None;
+    let mut endpos = None;
+    let mut json = false;
+    let mut show_unset = false;
+    let mut dump_ast = false;
+    let mut dump_program = false;
+    let mut trace = false;
+    let flags = ["--match", "--fullmatch", "--pos", "--endpos", "--format", "--show-unset", "--dump-ast", "--dump-program", "--trace"];
+    while let Some(flag) = args.first().filter(|a| flags.contains(&a.as_str())) {
+        let flag = flag.clone();
+        args.remove(0);
+        match flag.as_str() {
+            "--pos" | "--endpos" => {
+                let Some(n) = args.first().and_then(|a| a.parse::<usize>().ok()) else { usage() };
+                args.remove(0);
+                if flag == "--pos" { pos = Some(n) } else { endpos = Some(n) }
+            }
+            "--format" => {
+                json = match args.first().map(String::as_str) {
+                    Some("text") => false,
+                    Some("json") => true,
+                    _ => usage(),
+                };
+                args.remove(0);
+            }
+            "--show-unset" => show_unset = true,
+            "--dump-ast" => dump_ast = true,
+            "--dump-program" => dump_program = true,
+            "--trace" => trace = true,
+            _ => mode = Some(flag),
+        }
+    }
+    if args.len() != 2 {
+        usage();
+    }
+    // The trace follows one `search_from`; other modes would report matches
+    // it didn't show.
+    if trace && (mode.is_some() || json) {
+        eprintln!("--trace only applies to a plain search, not --match, --fullmatch or --format json");
         std::process::exit(1);
     }
 
-    let pattern = &args[1];
-    let input = &args[2];
+    let pattern = &args[0];
+    let input = &args[1];
 
     // Parse
     let mut p = parser::Parser::new(pattern);
     let ast = match p.parse() {
         Ok(ast) => ast,
         Err(e) => {
-            println!("ERROR:{}", e);
+            let span = p.error_span().unwrap_or((0, pattern.chars().count()));
+            print_error(&e, span, json);
             return;
         }
     };
 
+    if dump_ast {
+        print!("{}", dump::ast(&ast));
+    }
+
     let n_groups = p.group_count();
 
     // Compile
-    let program = compiler::compile(&ast, n_groups);
+    let program = match compiler::compile(&ast, n_groups) {
+        Ok(program) => program,
+        Err(e) => {
+            print_error(&e, (0, pattern.chars().count()), json);
+            return;
+        }
+    };
+
+    if dump_program {
+        print!("{}", dump::program(&program));
+    }
 
     // Execute
-    match vm::search(&program, input) {
+    let chars: Vec<char> = input.chars().collect();
+    // Python clamps both bounds to the input.
+    let start = pos.unwrap_or(0).min(chars.len());
+    let end = endpos.unwrap_or(chars.len()).min(chars.len());
+    let matches: Vec<MatchResult> = match mode.as_deref() {
+        Some("--match") => vm::match_at(&program, &chars[..end], start, None).into_iter().collect(),
+        Some("--fullmatch") => vm::match_at(&program, &chars[..end], start, Some(end)).into_iter().collect(),
+        _ if json => find_all(&program, &chars[..end], start),
+        _ if trace => traced_search(&program, &chars[..end], start).into_iter().collect(),
+        _ => vm::search_range(&program, &chars, start, end, vm::RangeOptions::PYTHON).into_iter().collect(),
+    };
+    if json {
+        print_json(&matches, &chars, n_groups, p.group_names());
+        return;
+    }
+    match matches.first() {
         Some(result) => {
             let matched: String = input.chars().skip(result.start).take(result.end - result.start).collect();
             println!("MATCH:{}", matched);
             // Print capturing groups
             for i in 1..=n_groups {
-                let start_slot = i * 2;
-                let end_slot = i * 2 + 1;
-                match (result.captures.get(start_slot).copied().flatten(),
-                       result.captures.get(end_slot).copied().flatten()) {
-                    (Some(s), Some(e)) => {
+                match result.group(i) {
+                    Some((s, e)) => {
                         let group_text: String = input.chars().skip(s).take(e - s).collect();
                         println!("GROUP {}:{}", i, group_text);
                     }
-                    _ => {
+                    None if show_unset => {
+                        println!("GROUP {}:<unset>", i);
+                    }
+                    None => {
                         println!("GROUP {}:", i);
                     }
                 }
@@ -56,3 +151,83 @@ fn main() {
         }
     }
 }
+
+/// Every match starting at or after `start`, as `finditer(text, pos)` finds
+/// them. Nothing if `start` is past the end.
+fn find_all(program: &Program, chars: &[char], start: usize) -> Vec<MatchResult> {
+    let mut found = Vec::new();
+    if start > chars.len() {
+        return found;
+    }
+    let mut pos = start;
+    let mut allow_empty = true;
+    while let Some(m) = vm::search_from(program, chars, pos, allow_empty) {
+        pos = m.end;
+        allow_empty = m.end > m.start;
+        found.push(m);
+    }
+    found
+}
+
+/// Search like `vm::search_from`, logging each VM event to stderr: the
+/// start positions tried, then every step (counter, pc, position and
+/// instruction) and every backtrack. Ends with the number of steps.
+fn traced_search(program: &Program, chars: &[char], start: usize) -> Option<MatchResult> {
+    if start > chars.len() {
+        return None;
+    }
+    let mut steps = 0;
+    let result = vm::search_traced(program, chars, start, |event| match event {
+        vm::TraceEvent::Attempt { pos } => eprintln!("attempt at {}", pos),
+        vm::TraceEvent::Step { pc, pos } => {
+            steps += 1;
+            eprintln!("{:>6}  pc {:>3}  pos {:>3}  {}", steps, pc, pos, program.insts[pc]);
+        }
+        vm::TraceEvent::Backtrack { pc, pos } => eprintln!("        backtrack from pc {} at pos {}", pc, pos),
+    });
+    eprintln!("{} steps", steps);
+    result
+}
+
+/// Report a pattern error: `ERROR:<message>`, or with `json` an object
+/// with the message and the span of the pattern (in chars) it is about.
+fn print_error(message: &str, (start, end): (usize, usize), json: bool) {
+    if json {
+        println!(
+            "{{\"error\":{{\"message\":{},\"start\":{},\"end\":{}}}}}",
+            cli::json::string(message),
+            start,
+            end
+        );
+    } else {
+        println!("ERROR:{}", message);
+    }
+}
+
+/// Print `{"matches":[...]}`: each match's char offsets and text, and one
+/// object per group whose offsets and text are null if it didn't take part.
+fn print_json(matches: &[MatchResult], chars: &[char], n_groups: usize, names: &[(String, usize)]) {
+    let span = |group: Option<(usize, usize)>| match group {
+        Some((s, e)) => {
+            let text: String = chars[s..e].iter().collect();
+            format!("\"start\":{},\"end\":{},\"text\":{}", s, e, cli::json::string(&text))
+        }
+        None => "\"start\":null,\"end\":null,\"text\":null".to_string(),
+    };
+    let objects: Vec<String> = matches
+        .iter()
+        .map(|m| {
+            let groups: Vec<String> = (1..=n_groups)
+                .map(|i| {
+                    let name = match names.iter().find(|(_, index)| *index == i) {
+                        Some((name, _)) => format!(",\"name\":{}", cli::json::string(name)),
+                        None => String::new(),
+                    };
+                    format!("{{\"group\":{}{},{}}}", i, name, span(m.group(i)))
+                })
+                .collect();
+            format!("{{{},\"groups\":[{}]}}", span(Some((m.start, m.end))), groups.join(","))
+        })
+        .collect();
+    println!("{{\"matches\":[{}]}}", objects.join(","));
+}
@@ -1,11 +1,25 @@
-/// Regex parser: converts a pattern string into an AST.
+//! Regex parser: converts a pattern string into an AST.
 
 use crate::ast::*;
 
+/// First placeholder group index of a call to a name not yet defined.
+const UNRESOLVED_CALL: usize = usize::MAX / 2;
+
 pub struct Parser {
     chars: Vec<char>,
     pos: usize,
     group_count: usize,
+    /// Named groups in order of definition: (name, group index).
+    group_names: Vec<(String, usize)>,
+    /// Highest group number referenced by a backreference or conditional,
+    /// validated against `group_count` once the whole pattern is parsed.
+    max_group_ref: usize,
+    /// Names called by `(?&name)`/`(?P>name)` before their group was
+    /// defined; the call's group index is `UNRESOLVED_CALL` plus the name's
+    /// position here until the end of the parse resolves it.
+    forward_calls: Vec<String>,
+    /// Set when parsing fails: see `error_span`.
+    error_span: Option<(usize, usize)>,
 }
 
 impl Parser {
@@ -14,26 +28,193 @@ impl Parser {
             chars: pattern.chars().collect(),
             pos: 0,
             group_count: 0,
+            group_names: Vec::new(),
+            max_group_ref: 0,
+            forward_calls: Vec::new(),
+            error_span: None,
         }
     }
 
     /// Parse the full pattern and return an AST node.
     pub fn parse(&mut self) -> Result<AstNode, String> {
-        let node = self.parse_alternation()?;
+        let mut node = self.parse_alternation()?;
         if self.pos < self.chars.len() {
+            self.error_span = Some((self.pos, self.pos + 1));
             return Err(format!(
                 "Unexpected character '{}' at position {}",
                 self.chars[self.pos], self.pos
             ));
         }
+        if self.max_group_ref > self.group_count {
+            self.error_span = Some((0, self.chars.len()));
+            return Err(format!("invalid group reference {}", self.max_group_ref));
+        }
+        if !self.forward_calls.is_empty() {
+            let groups = self.forward_calls.iter().map(|name| self.lookup_group_name(name)).collect::<Result<Vec<_>, _>>();
+            let groups = groups.inspect_err(|_| self.error_span = Some((0, self.chars.len())))?;
+            resolve_calls(&mut node, &groups);
+        }
         Ok(node)
     }
 
+    /// After `parse` fails, the char range of the pattern the error is about:
+    /// from the start of the innermost element that failed to parse to where
+    /// the problem was found (`(ab` gives the whole group, `a\q` the `\q`).
+    pub fn error_span(&self) -> Option<(usize, usize)> {
+        self.error_span
+    }
+
     /// Returns total number of capturing groups found.
     pub fn group_count(&self) -> usize {
         self.group_count
     }
 
+    /// Returns the named groups as (name, group index) pairs.
+    pub fn group_names(&self) -> &[(String, usize)] {
+        &self.group_names
+    }
+
+    /// Record a reference to group `index` for end-of-parse validation.
+    fn reference_group(&mut self, index: usize) -> usize {
+        self.max_group_ref = self.max_group_ref.max(index);
+        index
+    }
+
+    /// The group a named call refers to: resolved now if the name is
+    /// already defined, else once the whole pattern is parsed.
+    fn call_group_name(&mut self, name: String) -> usize {
+        if let Ok(index) = self.lookup_group_name(&name) {
+            return index;
+        }
+        let slot = match self.forward_calls.iter().position(|n| *n == name) {
+            Some(slot) => slot,
+            None => {
+                self.forward_calls.push(name);
+                self.forward_calls.len() - 1
+            }
+        };
+        UNRESOLVED_CALL + slot
+    }
+
+    /// Resolve a group name defined earlier in the pattern.
+    fn lookup_group_name(&self, name: &str) -> Result<usize, String> {
+        self.group_names
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, index)| index)
+            .ok_or_else(|| format!("unknown group name '{}'", name))
+    }
+
+    /// Parse a group name terminated by `terminator`, consuming both.
+    fn parse_group_name(&mut self, terminator: char) -> Result<String, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != terminator) {
+            self.advance();
+        }
+        if self.peek().is_none() {
+            return Err("missing group name terminator".to_string());
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        self.advance(); // consume terminator
+        let mut name_chars = name.chars();
+        let valid = name_chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name_chars.all(|c| c.is_alphanumeric() || c == '_');
+        if valid {
+            Ok(name)
+        } else if name.is_empty() {
+            Err("missing group name".to_string())
+        } else {
+            Err(format!("bad character in group name '{}'", name))
+        }
+    }
+
+    /// Parse the body of a capturing group (after its opening syntax).
+    fn parse_capturing_group(&mut self, name: Option<String>) -> Result<AstNode, String> {
+        self.group_count += 1;
+        let index = self.group_count;
+        if let Some(name) = name {
+            // A name may repeat only for the same group number, which happens
+            // in different alternatives of a branch-reset group.
+            match self.group_names.iter().find(|(n, _)| *n == name) {
+                Some(&(_, existing)) if existing == index => {}
+                Some(_) => return Err(format!("redefinition of group name '{}'", name)),
+                None => self.group_names.push((name, index)),
+            }
+        }
+        let node = self.parse_alternation()?;
+        self.expect(')')?;
+        Ok(AstNode::Group {
+            index,
+            node: Box::new(node),
+        })
+    }
+
+    /// Parse a branch-reset group `(?|a(x)|b(y))` after the `(?|` prefix.
+    /// Every alternative numbers its groups from the same starting index, so
+    /// the group count afterwards is the maximum over the alternatives.
+    fn parse_branch_reset(&mut self) -> Result<AstNode, String> {
+        let base = self.group_count;
+        let mut max_count = base;
+        let mut branches = Vec::new();
+        loop {
+            self.group_count = base;
+            branches.push(self.parse_concat()?);
+            max_count = max_count.max(self.group_count);
+            if self.peek() == Some('|') {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.group_count = max_count;
+        self.expect(')')?;
+        let node = if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            AstNode::Alternation(branches)
+        };
+        Ok(AstNode::NonCapturingGroup {
+            node: Box::new(node),
+        })
+    }
+
+    /// Parse a conditional `(?(cond)yes|no)` after the `(?(` prefix. The
+    /// condition is a group number or a group name.
+    fn parse_conditional(&mut self) -> Result<AstNode, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != ')') {
+            self.advance();
+        }
+        let cond: String = self.chars[start..self.pos].iter().collect();
+        self.expect(')')?;
+        let group = if !cond.is_empty() && cond.chars().all(|c| c.is_ascii_digit()) {
+            let index = cond.parse::<usize>().map_err(|e| e.to_string())?;
+            if index == 0 {
+                return Err("bad group number".to_string());
+            }
+            self.reference_group(index)
+        } else {
+            self.lookup_group_name(&cond)?
+        };
+        let yes = self.parse_concat()?;
+        let no = if self.peek() == Some('|') {
+            self.advance();
+            let no = self.parse_concat()?;
+            if self.peek() == Some('|') {
+                return Err("conditional backref with more than two branches".to_string());
+            }
+            Some(Box::new(no))
+        } else {
+            None
+        };
+        self.expect(')')?;
+        Ok(AstNode::Conditional {
+            group,
+            yes: Box::new(yes),
+            no,
+        })
+    }
+
     fn peek(&self) -> Option<char> {
         self.chars.get(self.pos).copied()
     }
@@ -75,7 +256,16 @@ impl Parser {
             if ch == ')' || ch == '|' {
                 break;
             }
-            nodes.push(self.parse_quantified()?);
+            let start = self.pos;
+            match self.parse_quantified() {
+                Ok(node) => nodes.push(node),
+                Err(e) => {
+                    // The innermost failing element is recorded first.
+                    let end = self.pos.max(start + 1).min(self.chars.len());
+                    self.error_span.get_or_insert((start, end));
+                    return Err(e);
+                }
+            }
         }
         if nodes.len() == 1 {
             Ok(nodes.pop().unwrap())
@@ -103,11 +293,7 @@ impl Parser {
                     } else {
                         true
                     };
-                    Ok(AstNode::Quantifier {
-                        node: Box::new(node),
-                        kind,
-                        greedy,
-                    })
+                    Ok(self.finish_quantifier(node, kind, greedy))
                 }
                 '{' => self.parse_brace_quantifier(node),
                 _ => Ok(node),
@@ -117,6 +303,24 @@ impl Parser {
         }
     }
 
+    /// Build the quantifier node, consuming a trailing `+` that makes a greedy
+    /// quantifier possessive. `x*+` is sugar for the atomic group `(?>x*)`.
+    fn finish_quantifier(&mut self, node: AstNode, kind: QuantifierKind, greedy: bool) -> AstNode {
+        let quantified = AstNode::Quantifier {
+            node: Box::new(node),
+            kind,
+            greedy,
+        };
+        if greedy && self.peek() == Some('+') {
+            self.advance();
+            AstNode::AtomicGroup {
+                node: Box::new(quantified),
+            }
+        } else {
+            quantified
+        }
+    }
+
     /// Parse `{n}`, `{n,}`, `{n,m}` quantifier.
     fn parse_brace_quantifier(&mut self, node: AstNode) -> Result<AstNode, String> {
         let save_pos = self.pos;
@@ -126,16 +330,12 @@ impl Parser {
         match self.try_parse_brace_contents() {
             Ok((kind, greedy)) => {
                 // Validate range: min must not exceed max
-                if let QuantifierKind::Range(n, m) = &kind {
-                    if n > m {
-                        return Err(format!("min repeat greater than max repeat"));
-                    }
+                if let QuantifierKind::Range(n, m) = &kind
+                    && n > m
+                {
+                    return Err("min repeat greater than max repeat".to_string());
                 }
-                Ok(AstNode::Quantifier {
-                    node: Box::new(node),
-                    kind,
-                    greedy,
-                })
+                Ok(self.finish_quantifier(node, kind, greedy))
             }
             Err(_) => {
                 // Not a valid quantifier, revert position — the '{' was a literal
@@ -223,7 +423,10 @@ impl Parser {
             Some('S') => Ok(AstNode::ShorthandClass(ShorthandKind::NonSpace)),
             Some('b') => Ok(AstNode::Anchor(AnchorKind::WordBoundary)),
             Some('B') => Ok(AstNode::Anchor(AnchorKind::NonWordBoundary)),
-            Some(ch) if ch.is_ascii_digit() && ch != '0' => {
+            Some('A') => Ok(AstNode::Anchor(AnchorKind::StartText)),
+            Some('z') | Some('Z') => Ok(AstNode::Anchor(AnchorKind::EndText)),
+            Some('G') => Ok(AstNode::Anchor(AnchorKind::SearchStart)),
+            Some(ch) if ch.is_ascii_digit() && ch != '0' && !self.at_octal_escape(ch) => {
                 // Backreference \1, \2, ..., \99, etc. (multi-digit)
                 let mut num = (ch as u8 - b'0') as usize;
                 while let Some(next) = self.peek() {
@@ -234,19 +437,111 @@ impl Parser {
                         break;
                     }
                 }
-                Ok(AstNode::Backreference(num))
+                Ok(AstNode::Backreference(self.reference_group(num)))
             }
-            Some('n') => Ok(AstNode::Literal('\n')),
-            Some('r') => Ok(AstNode::Literal('\r')),
-            Some('t') => Ok(AstNode::Literal('\t')),
-            Some(ch) => {
-                // Escaped literal: \., \*, \\, etc.
-                Ok(AstNode::Literal(ch))
+            Some(ch) => Ok(AstNode::Literal(self.parse_char_escape(ch, false)?)),
+        }
+    }
+
+    /// Whether `\<first>` begins a three-digit octal escape (`\101`) rather
+    /// than a backreference. `self.pos` is just past `first`.
+    fn at_octal_escape(&self, first: char) -> bool {
+        let is_oct = |c: Option<&char>| c.is_some_and(|c| ('0'..='7').contains(c));
+        is_oct(Some(&first)) && is_oct(self.chars.get(self.pos)) && is_oct(self.chars.get(self.pos + 1))
+    }
+
+    /// Decode a single-character escape whose introducer `ch` (the character
+    /// after `\`) has already been consumed. Shared by atoms and classes;
+    /// `in_class` selects Python's class rules (`\b` is backspace and any
+    /// digit starts an octal escape).
+    fn parse_char_escape(&mut self, ch: char, in_class: bool) -> Result<char, String> {
+        match ch {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            'a' => Ok('\x07'),
+            'f' => Ok('\x0C'),
+            'v' => Ok('\x0B'),
+            'b' if in_class => Ok('\x08'),
+            '0'..='7' => {
+                // Up to three octal digits in total: \0, \012, \101.
+                let mut value = ch as u32 - '0' as u32;
+                let mut digits = 1;
+                while digits < 3 {
+                    match self.peek() {
+                        Some(c @ '0'..='7') => {
+                            value = value * 8 + (c as u32 - '0' as u32);
+                            self.advance();
+                            digits += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                if value > 0o377 {
+                    return Err(format!("octal escape value \\{:o} outside of range 0-0o377", value));
+                }
+                Ok(char::from_u32(value).unwrap())
+            }
+            '8' | '9' if in_class => Err(format!("bad escape \\{}", ch)),
+            'x' if self.peek() == Some('{') => {
+                self.advance(); // consume '{'
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    self.advance();
+                }
+                let digits: String = self.chars[start..self.pos].iter().collect();
+                if digits.is_empty() || digits.len() > 6 || self.peek() != Some('}') {
+                    return Err(format!("bad escape \\x{{{}", digits));
+                }
+                self.advance(); // consume '}'
+                let value = u32::from_str_radix(&digits, 16).unwrap();
+                char::from_u32(value).ok_or_else(|| format!("bad escape \\x{{{}}}", digits))
             }
+            'x' => self.parse_hex_escape('x', 2),
+            'u' => self.parse_hex_escape('u', 4),
+            'U' => self.parse_hex_escape('U', 8),
+            'N' => {
+                if self.peek() != Some('{') {
+                    return Err("missing {".to_string());
+                }
+                self.advance(); // consume '{'
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.advance();
+                }
+                if self.peek().is_none() {
+                    return Err("missing }, unterminated name".to_string());
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                self.advance(); // consume '}'
+                unicode_char_by_name(&name).ok_or_else(|| {
+                    format!("undefined character name '{}' (only ASCII and common typographic names are known)", name)
+                })
+            }
+            // Python reserves the remaining ASCII letters for future escapes.
+            _ if ch.is_ascii_alphabetic() => Err(format!("bad escape \\{}", ch)),
+            // Escaped literal: \., \*, \\, etc.
+            _ => Ok(ch),
+        }
+    }
+
+    /// Parse exactly `len` hex digits following `\x`, `\u` or `\U`.
+    fn parse_hex_escape(&mut self, kind: char, len: usize) -> Result<char, String> {
+        let start = self.pos;
+        while self.pos - start < len && self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.advance();
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        if digits.len() < len {
+            return Err(format!("incomplete escape \\{}{}", kind, digits));
         }
+        let value = u32::from_str_radix(&digits, 16).unwrap();
+        char::from_u32(value).ok_or_else(|| format!("bad escape \\{}{}", kind, digits))
     }
 
-    /// Parse a group: `(...)`, `(?:...)`, `(?=...)`, `(?!...)`, `(?<=...)`, `(?<!...)`.
+    /// Parse a group: `(...)`, `(?:...)`, `(?|...)`, `(?>...)`, `(?=...)`, `(?!...)`, `(?<=...)`, `(?<!...)`,
+    /// named groups `(?P<name>...)` / `(?<name>...)`, `(?P=name)`, conditionals `(?(1)...)`
+    /// and subroutine calls `(?R)`, `(?1)`, `(?-1)`, `(?&name)`, `(?P>name)`.
     fn parse_group(&mut self) -> Result<AstNode, String> {
         self.advance(); // consume '('
 
@@ -261,6 +556,18 @@ impl Parser {
                         node: Box::new(node),
                     })
                 }
+                Some('|') => {
+                    self.advance();
+                    self.parse_branch_reset()
+                }
+                Some('>') => {
+                    self.advance();
+                    let node = self.parse_alternation()?;
+                    self.expect(')')?;
+                    Ok(AstNode::AtomicGroup {
+                        node: Box::new(node),
+                    })
+                }
                 Some('=') => {
                     self.advance();
                     let node = self.parse_alternation()?;
@@ -300,9 +607,66 @@ impl Parser {
                                 positive: false,
                             })
                         }
-                        _ => Err("Invalid lookbehind syntax".to_string()),
+                        // (?<name>...) — named capturing group
+                        _ => {
+                            let name = self.parse_group_name('>')?;
+                            self.parse_capturing_group(Some(name))
+                        }
+                    }
+                }
+                Some('P') => {
+                    self.advance(); // consume 'P'
+                    match self.advance() {
+                        // (?P<name>...) — named capturing group
+                        Some('<') => {
+                            let name = self.parse_group_name('>')?;
+                            self.parse_capturing_group(Some(name))
+                        }
+                        // (?P=name) — named backreference
+                        Some('=') => {
+                            let name = self.parse_group_name(')')?;
+                            Ok(AstNode::Backreference(self.lookup_group_name(&name)?))
+                        }
+                        // (?P>name) — named subroutine call
+                        Some('>') => {
+                            let name = self.parse_group_name(')')?;
+                            Ok(AstNode::Subroutine(self.call_group_name(name)))
+                        }
+                        _ => Err("unknown extension ?P".to_string()),
                     }
                 }
+                Some('(') => {
+                    self.advance(); // consume '('
+                    self.parse_conditional()
+                }
+                // (?R), (?0) — recurse into the whole pattern
+                Some('R') => {
+                    self.advance();
+                    self.expect(')')?;
+                    Ok(AstNode::Subroutine(0))
+                }
+                // (?1), (?+1), (?-1) — call a group by absolute or relative number
+                Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => {
+                    if c == '+' || c == '-' {
+                        self.advance();
+                    }
+                    let n = self.parse_number()?;
+                    self.expect(')')?;
+                    let group = match c {
+                        // (?+1) is the next group to be opened, (?-1) the most recent one.
+                        '+' if n > 0 => self.group_count + n,
+                        '-' if n > 0 && n <= self.group_count => self.group_count + 1 - n,
+                        '+' | '-' => return Err("invalid relative group reference".to_string()),
+                        _ => n,
+                    };
+                    Ok(AstNode::Subroutine(self.reference_group(group)))
+                }
+                // (?&name) — call a named group
+                Some('&') => {
+                    self.advance();
+                    let name = self.parse_group_name(')')?;
+                    Ok(AstNode::Subroutine(self.call_group_name(name)))
+                }
                 Some('i') => {
                     self.advance(); // consume 'i'
                     if self.peek() == Some(':') {
@@ -321,14 +685,7 @@ impl Parser {
             }
         } else {
             // Capturing group
-            self.group_count += 1;
-            let index = self.group_count;
-            let node = self.parse_alternation()?;
-            self.expect(')')?;
-            Ok(AstNode::Group {
-                index,
-                node: Box::new(node),
-            })
+            self.parse_capturing_group(None)
         }
     }
 
@@ -350,48 +707,28 @@ impl Parser {
         }
 
         while self.peek() != Some(']') {
-            match self.peek() {
-                None => return Err("Unterminated character class".to_string()),
-                Some('\\') => {
-                    self.advance();
-                    match self.advance() {
-                        None => return Err("Unexpected end in character class escape".to_string()),
-                        Some('d') => items.push(ClassItem::Shorthand(ShorthandKind::Digit)),
-                        Some('D') => items.push(ClassItem::Shorthand(ShorthandKind::NonDigit)),
-                        Some('w') => items.push(ClassItem::Shorthand(ShorthandKind::Word)),
-                        Some('W') => items.push(ClassItem::Shorthand(ShorthandKind::NonWord)),
-                        Some('s') => items.push(ClassItem::Shorthand(ShorthandKind::Space)),
-                        Some('S') => items.push(ClassItem::Shorthand(ShorthandKind::NonSpace)),
-                        Some('n') => items.push(ClassItem::Literal('\n')),
-                        Some('r') => items.push(ClassItem::Literal('\r')),
-                        Some('t') => items.push(ClassItem::Literal('\t')),
-                        Some(ch) => items.push(ClassItem::Literal(ch)),
-                    }
+            if self.peek().is_none() {
+                return Err("Unterminated character class".to_string());
+            }
+            let ch = match self.parse_class_atom()? {
+                ClassItem::Literal(ch) => ch,
+                item => {
+                    items.push(item);
+                    continue;
                 }
-                Some(ch) => {
-                    self.advance();
-                    // Check for range like a-z
-                    if self.peek() == Some('-')
-                        && self.pos + 1 < self.chars.len()
-                        && self.chars[self.pos + 1] != ']'
-                    {
-                        self.advance(); // consume '-'
-                        let end_ch = match self.peek() {
-                            Some('\\') => {
-                                self.advance();
-                                self.advance().ok_or("Unexpected end in range")?
-                            }
-                            Some(c) => {
-                                self.advance();
-                                c
-                            }
-                            None => return Err("Unexpected end in character class range".to_string()),
-                        };
-                        items.push(ClassItem::Range(ch, end_ch));
-                    } else {
-                        items.push(ClassItem::Literal(ch));
-                    }
+            };
+            // Check for range like a-z
+            if self.peek() == Some('-')
+                && self.pos + 1 < self.chars.len()
+                && self.chars[self.pos + 1] != ']'
+            {
+                self.advance(); // consume '-'
+                match self.parse_class_atom()? {
+                    ClassItem::Literal(end_ch) => items.push(ClassItem::Range(ch, end_ch)),
+                    _ => return Err("bad character range".to_string()),
                 }
+            } else {
+                items.push(ClassItem::Literal(ch));
             }
         }
         self.advance(); // consume ']'
@@ -400,4 +737,143 @@ impl Parser {
             negated,
         })
     }
+
+    /// Parse one class element: a literal character (possibly escaped) or a
+    /// shorthand like `\d`. Never returns `ClassItem::Range`.
+    fn parse_class_atom(&mut self) -> Result<ClassItem, String> {
+        match self.advance() {
+            None => Err("Unexpected end in character class".to_string()),
+            Some('\\') => match self.advance() {
+                None => Err("Unexpected end in character class escape".to_string()),
+                Some('d') => Ok(ClassItem::Shorthand(ShorthandKind::Digit)),
+                Some('D') => Ok(ClassItem::Shorthand(ShorthandKind::NonDigit)),
+                Some('w') => Ok(ClassItem::Shorthand(ShorthandKind::Word)),
+                Some('W') => Ok(ClassItem::Shorthand(ShorthandKind::NonWord)),
+                Some('s') => Ok(ClassItem::Shorthand(ShorthandKind::Space)),
+                Some('S') => Ok(ClassItem::Shorthand(ShorthandKind::NonSpace)),
+                Some(ch) => Ok(ClassItem::Literal(self.parse_char_escape(ch, true)?)),
+            },
+            Some(ch) => Ok(ClassItem::Literal(ch)),
+        }
+    }
+}
+
+/// Point calls recorded in `Parser::forward_calls` at their groups, now
+/// that every name is defined: `groups[k]` is the group of the k-th name.
+fn resolve_calls(node: &mut AstNode, groups: &[usize]) {
+    match node {
+        AstNode::Subroutine(group) if *group >= UNRESOLVED_CALL => *group = groups[*group - UNRESOLVED_CALL],
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => {
+            for n in nodes {
+                resolve_calls(n, groups);
+            }
+        }
+        AstNode::Quantifier { node, .. }
+        | AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::Lookahead { node, .. }
+        | AstNode::Lookbehind { node, .. }
+        | AstNode::CaseInsensitive { node } => resolve_calls(node, groups),
+        AstNode::Conditional { yes, no, .. } => {
+            resolve_calls(yes, groups);
+            if let Some(no) = no {
+                resolve_calls(no, groups);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Look up a character by its Unicode name for `\N{...}`. Names are matched
+/// case-insensitively. Without a bundled Unicode database this covers ASCII
+/// (letters, digits, punctuation and common control aliases) plus a handful
+/// of frequently used typographic characters.
+fn unicode_char_by_name(name: &str) -> Option<char> {
+    const NAMED: &[(&str, char)] = &[
+        ("NULL", '\0'),
+        ("BELL", '\x07'),
+        ("BACKSPACE", '\x08'),
+        ("CHARACTER TABULATION", '\t'),
+        ("LINE FEED", '\n'),
+        ("LINE TABULATION", '\x0B'),
+        ("FORM FEED", '\x0C'),
+        ("CARRIAGE RETURN", '\r'),
+        ("ESCAPE", '\x1B'),
+        ("SPACE", ' '),
+        ("EXCLAMATION MARK", '!'),
+        ("QUOTATION MARK", '"'),
+        ("NUMBER SIGN", '#'),
+        ("DOLLAR SIGN", '$'),
+        ("PERCENT SIGN", '%'),
+        ("AMPERSAND", '&'),
+        ("APOSTROPHE", '\''),
+        ("LEFT PARENTHESIS", '('),
+        ("RIGHT PARENTHESIS", ')'),
+        ("ASTERISK", '*'),
+        ("PLUS SIGN", '+'),
+        ("COMMA", ','),
+        ("HYPHEN-MINUS", '-'),
+        ("FULL STOP", '.'),
+        ("SOLIDUS", '/'),
+        ("COLON", ':'),
+        ("SEMICOLON", ';'),
+        ("LESS-THAN SIGN", '<'),
+        ("EQUALS SIGN", '='),
+        ("GREATER-THAN SIGN", '>'),
+        ("QUESTION MARK", '?'),
+        ("COMMERCIAL AT", '@'),
+        ("LEFT SQUARE BRACKET", '['),
+        ("REVERSE SOLIDUS", '\\'),
+        ("RIGHT SQUARE BRACKET", ']'),
+        ("CIRCUMFLEX ACCENT", '^'),
+        ("LOW LINE", '_'),
+        ("GRAVE ACCENT", '`'),
+        ("LEFT CURLY BRACKET", '{'),
+        ("VERTICAL LINE", '|'),
+        ("RIGHT CURLY BRACKET", '}'),
+        ("TILDE", '~'),
+        ("DELETE", '\x7F'),
+        ("NO-BREAK SPACE", '\u{A0}'),
+        ("SECTION SIGN", '\u{A7}'),
+        ("COPYRIGHT SIGN", '\u{A9}'),
+        ("REGISTERED SIGN", '\u{AE}'),
+        ("DEGREE SIGN", '\u{B0}'),
+        ("MICRO SIGN", '\u{B5}'),
+        ("ZERO WIDTH SPACE", '\u{200B}'),
+        ("EN DASH", '\u{2013}'),
+        ("EM DASH", '\u{2014}'),
+        ("LEFT SINGLE QUOTATION MARK", '\u{2018}'),
+        ("RIGHT SINGLE QUOTATION MARK", '\u{2019}'),
+        ("LEFT DOUBLE QUOTATION MARK", '\u{201C}'),
+        ("RIGHT DOUBLE QUOTATION MARK", '\u{201D}'),
+        ("BULLET", '\u{2022}'),
+        ("HORIZONTAL ELLIPSIS", '\u{2026}'),
+        ("EURO SIGN", '\u{20AC}'),
+        ("REPLACEMENT CHARACTER", '\u{FFFD}'),
+        ("ZERO WIDTH NO-BREAK SPACE", '\u{FEFF}'),
+    ];
+    const DIGITS: [&str; 10] = [
+        "ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE",
+    ];
+
+    let name = name.to_ascii_uppercase();
+    if let Some(&(_, ch)) = NAMED.iter().find(|(n, _)| *n == name) {
+        return Some(ch);
+    }
+    if let Some(digit) = name.strip_prefix("DIGIT ") {
+        let i = DIGITS.iter().position(|d| *d == digit)?;
+        return Some((b'0' + i as u8) as char);
+    }
+    let (letter, upper) = if let Some(l) = name.strip_prefix("LATIN CAPITAL LETTER ") {
+        (l, true)
+    } else if let Some(l) = name.strip_prefix("LATIN SMALL LETTER ") {
+        (l, false)
+    } else {
+        return None;
+    };
+    match letter.as_bytes() {
+        [b @ b'A'..=b'Z'] => Some(if upper { *b as char } else { b.to_ascii_lowercase() as char }),
+        _ => None,
+    }
 }
@@ -0,0 +1,213 @@
+//! Non-backtracking (Pike VM) executor for combined multi-pattern programs.
+//!
+//! All threads advance over the input in lockstep, so each position is read
+//! once however many patterns there are. Threads are kept in priority order
+//! (earlier start first, then alternation preference), which gives every
+//! pattern the same leftmost-first match the backtracking VM finds. Only the
+//! overall match span is tracked, not capture groups.
+//!
+//! Instructions that need backtracking state (backreferences, lookaround,
+//! atomic groups, calls, counted loops, conditionals) are not supported; see
+//! `supports`.
+
+use crate::compiler::{Entry, Inst, MultiProgram};
+use crate::vm::{char_eq, is_word_boundary, shorthand_matches};
+
+/// Whether the Pike VM can run a program made of `insts`.
+pub fn supports(insts: &[Inst]) -> bool {
+    insts.iter().all(|inst| {
+        matches!(
+            inst,
+            Inst::Char(_)
+                | Inst::Str(_)
+                | Inst::AnyChar
+                | Inst::CharClass(_)
+                | Inst::ShorthandClass(_)
+                | Inst::Match
+                | Inst::MatchPattern(_)
+                | Inst::Jump(_)
+                | Inst::Split(_, _)
+                | Inst::Save(_)
+                | Inst::AssertStart
+                | Inst::AssertEnd
+                | Inst::AssertTextStart
+                | Inst::AssertTextEnd
+                | Inst::AssertSearchStart
+                | Inst::AssertWordBoundary
+                | Inst::AssertNonWordBoundary
+                | Inst::Nop
+                | Inst::CaseInsensitiveOn
+                | Inst::CaseInsensitiveOff
+        )
+    })
+}
+
+/// A thread waiting at a consuming instruction (or a `MatchPattern`).
+#[derive(Clone, Copy)]
+struct Thread {
+    pc: usize,
+    /// Offset of the next character to compare inside a `Str`.
+    k: usize,
+    /// Where this thread's match started.
+    start: usize,
+    /// Pattern the thread belongs to.
+    id: usize,
+    ci_depth: usize,
+}
+
+/// A combined program prepared for the Pike VM.
+pub struct PikeVm {
+    multi: MultiProgram,
+    /// State number of each instruction; a `Str` of length n takes n states,
+    /// one per character still to compare.
+    base: Vec<usize>,
+    n_states: usize,
+    n_ids: usize,
+}
+
+impl PikeVm {
+    /// Prepare `multi`, whose pattern ids are all below `n_ids`. Every
+    /// instruction must be `supports`ed.
+    pub fn new(multi: MultiProgram, n_ids: usize) -> PikeVm {
+        let mut base = Vec::with_capacity(multi.insts.len());
+        let mut n_states = 0;
+        for inst in &multi.insts {
+            base.push(n_states);
+            n_states += match inst {
+                Inst::Str(text) => text.len(),
+                _ => 1,
+            };
+        }
+        PikeVm { multi, base, n_states, n_ids }
+    }
+
+    /// The leftmost-first match `(start, end)` of every pattern, indexed by id.
+    /// Patterns whose id is not `active` are skipped and report `None`.
+    ///
+    /// With `earliest`, a pattern stops at its first match instead of
+    /// extending it, which is enough to know whether it matches at all.
+    pub fn find_each(&self, chars: &[char], active: &[bool], earliest: bool) -> Vec<Option<(usize, usize)>> {
+        let mut found: Vec<Option<(usize, usize)>> = vec![None; self.n_ids];
+        let mut done = vec![false; self.n_ids];
+        // `cut[id] == tag(pos)` once a thread of `id` matched at `pos`.
+        let mut cut = vec![0usize; self.n_ids];
+        // `seen[state] == tag(pos)` once the state is in the list for `pos`.
+        let mut seen = vec![0usize; self.n_states];
+        let mut clist: Vec<Thread> = Vec::new();
+        let mut nlist: Vec<Thread> = Vec::new();
+        let mut stack: Vec<Thread> = Vec::new();
+        // Entries that may still start a match.
+        let mut live: Vec<&Entry> = self.multi.entries.iter().filter(|e| active[e.id]).collect();
+
+        for pos in 0..=chars.len() {
+            let tag = pos + 1;
+            // New threads start at the lowest priority, and only for
+            // patterns without a match yet (a later start can't be leftmost).
+            // Threads of different patterns never share a state, so the
+            // order between patterns doesn't matter.
+            live.retain(|e| found[e.id].is_none() && (!e.anchored_start || pos == 0));
+            for e in &live {
+                let can_start = match &e.first_set {
+                    Some(set) => chars.get(pos).is_some_and(|&c| set.contains(c)),
+                    None => true,
+                };
+                if can_start {
+                    let t = Thread { pc: e.pc, k: 0, start: pos, id: e.id, ci_depth: 0 };
+                    self.add(&mut clist, &mut seen, &mut stack, t, chars, pos);
+                }
+            }
+            if clist.is_empty() && live.is_empty() {
+                break;
+            }
+            for t in clist.drain(..) {
+                if cut[t.id] == tag || done[t.id] {
+                    continue;
+                }
+                let inst = &self.multi.insts[t.pc];
+                if let Inst::MatchPattern(id) = inst {
+                    found[*id] = Some((t.start, pos));
+                    // Lower-priority threads of this pattern lose.
+                    cut[*id] = tag;
+                    done[*id] = earliest;
+                    continue;
+                }
+                let Some(&ch) = chars.get(pos) else { continue };
+                let ci = t.ci_depth > 0;
+                let next = match inst {
+                    Inst::Char(c) if char_eq(ch, *c, ci) => Thread { pc: t.pc + 1, k: 0, ..t },
+                    Inst::Str(text) if char_eq(ch, text[t.k], ci) => {
+                        if t.k + 1 < text.len() {
+                            Thread { k: t.k + 1, ..t }
+                        } else {
+                            Thread { pc: t.pc + 1, k: 0, ..t }
+                        }
+                    }
+                    Inst::AnyChar if ch != '\n' => Thread { pc: t.pc + 1, ..t },
+                    Inst::CharClass(set) if set.contains(ch) => Thread { pc: t.pc + 1, ..t },
+                    Inst::ShorthandClass(kind) if shorthand_matches(ch, *kind) => Thread { pc: t.pc + 1, ..t },
+                    _ => continue,
+                };
+                self.add(&mut nlist, &mut seen, &mut stack, next, chars, pos + 1);
+            }
+            std::mem::swap(&mut clist, &mut nlist);
+        }
+        found
+    }
+
+    /// Add `t` and everything reachable from it without consuming input to
+    /// `list`, in priority order. `pos` is the position the list is for.
+    /// `stack` is scratch space.
+    fn add(
+        &self,
+        list: &mut Vec<Thread>,
+        seen: &mut [usize],
+        stack: &mut Vec<Thread>,
+        t: Thread,
+        chars: &[char],
+        pos: usize,
+    ) {
+        let tag = pos + 1;
+        stack.push(t);
+        while let Some(t) = stack.pop() {
+            let state = self.base[t.pc] + t.k;
+            if seen[state] == tag {
+                continue;
+            }
+            seen[state] = tag;
+            let next = Thread { pc: t.pc + 1, ..t };
+            match &self.multi.insts[t.pc] {
+                Inst::Jump(target) => stack.push(Thread { pc: *target, ..t }),
+                Inst::Split(a, b) => {
+                    // Pushed in reverse so `a` is explored first.
+                    stack.push(Thread { pc: *b, ..t });
+                    stack.push(Thread { pc: *a, ..t });
+                }
+                Inst::Save(_) | Inst::Nop => stack.push(next),
+                Inst::AssertStart | Inst::AssertTextStart | Inst::AssertSearchStart => {
+                    if pos == 0 {
+                        stack.push(next);
+                    }
+                }
+                Inst::AssertEnd | Inst::AssertTextEnd => {
+                    if pos == chars.len() {
+                        stack.push(next);
+                    }
+                }
+                Inst::AssertWordBoundary => {
+                    if is_word_boundary(chars, pos) {
+                        stack.push(next);
+                    }
+                }
+                Inst::AssertNonWordBoundary => {
+                    if !is_word_boundary(chars, pos) {
+                        stack.push(next);
+                    }
+                }
+                Inst::CaseInsensitiveOn => stack.push(Thread { ci_depth: t.ci_depth + 1, ..next }),
+                Inst::CaseInsensitiveOff => stack.push(Thread { ci_depth: t.ci_depth.saturating_sub(1), ..next }),
+                // Consuming instructions and pattern ends wait in the list.
+                _ => list.push(t),
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! High-level API: a compiled pattern plus search and iteration helpers.
+//!
+//! All positions are character offsets into the input, as in `vm::MatchResult`.
+
+use crate::compiler::{self, Program};
+use crate::parser::Parser;
+use crate::replace::Template;
+use crate::stream::{self, StreamSearcher};
+use crate::vm::{self, MatchResult, RangeOptions};
+use std::io::{self, Read};
+
+/// A compiled regular expression.
+pub struct Regex {
+    program: Program,
+    /// Named groups: (name, group index).
+    group_names: Vec<(String, usize)>,
+}
+
+impl Regex {
+    /// Parse and compile `pattern`.
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser::new(pattern);
+        let ast = parser.parse()?;
+        let program = compiler::compile(&ast, parser.group_count())?;
+        Ok(Regex { program, group_names: parser.group_names().to_vec() })
+    }
+
+    /// Number of capturing groups in the pattern.
+    pub fn group_count(&self) -> usize {
+        self.program.n_groups
+    }
+
+    /// Find the leftmost match in `input` (like `re.search`).
+    pub fn search(&self, input: &str) -> Option<MatchResult> {
+        vm::search(&self.program, input)
+    }
+
+    /// Find the leftmost match starting at or after char offset `pos`.
+    /// Unlike searching a re-sliced string, lookbehind and `\b` still see
+    /// the text before `pos`; `\G` matches at `pos`.
+    pub fn search_at(&self, input: &str, pos: usize) -> Option<MatchResult> {
+        let chars: Vec<char> = input.chars().collect();
+        if pos > chars.len() {
+            return None;
+        }
+        vm::search_from(&self.program, &chars, pos, true)
+    }
+
+    /// Find the leftmost match within chars `start..end` of `input`. Whether
+    /// `^`, `$`, `\b` and lookarounds see past each edge is set by `options`
+    /// (`RangeOptions::PYTHON` behaves like `pattern.search(s, pos, endpos)`).
+    pub fn search_range(&self, input: &str, start: usize, end: usize, options: RangeOptions) -> Option<MatchResult> {
+        let chars: Vec<char> = input.chars().collect();
+        vm::search_range(&self.program, &chars, start, end, options)
+    }
+
+    /// Match starting exactly at char offset `pos` (like `re.match`, which is
+    /// `match_at(input, 0)`). `^` still only matches at the start of `input`.
+    pub fn match_at(&self, input: &str, pos: usize) -> Option<MatchResult> {
+        let chars: Vec<char> = input.chars().collect();
+        vm::match_at(&self.program, &chars, pos, None)
+    }
+
+    /// Whether the pattern matches starting exactly at char offset `pos`.
+    pub fn is_match_at(&self, input: &str, pos: usize) -> bool {
+        self.match_at(input, pos).is_some()
+    }
+
+    /// Match the whole of `input` (like `re.fullmatch`): the match must start
+    /// at 0 and end at the end, backtracking into other alternatives if the
+    /// preferred one stops short.
+    pub fn fullmatch(&self, input: &str) -> Option<MatchResult> {
+        let chars: Vec<char> = input.chars().collect();
+        vm::match_at(&self.program, &chars, 0, Some(chars.len()))
+    }
+
+    /// A searcher for input that arrives in chunks; it reports the same
+    /// matches as `find_iter` on the whole input, with absolute offsets.
+    pub fn stream(&self) -> StreamSearcher<'_> {
+        StreamSearcher::new(&self.program)
+    }
+
+    /// Call `on_match` for each match in the UTF-8 text read from `reader`
+    /// (like `find_iter`), without holding the whole input in memory.
+    pub fn stream_matches<R: Read>(&self, reader: R, on_match: impl FnMut(MatchResult)) -> io::Result<()> {
+        stream::search_reader(&self.program, reader, on_match)
+    }
+
+    /// Replace the first match with `template` (see `replacen`).
+    pub fn replace(&self, input: &str, template: &str) -> Result<String, String> {
+        self.replacen(input, template, 1)
+    }
+
+    /// Replace every match with `template` (like `re.sub`).
+    pub fn replace_all(&self, input: &str, template: &str) -> Result<String, String> {
+        self.replacen(input, template, 0)
+    }
+
+    /// Replace the first `limit` matches (all of them if `limit` is 0), in
+    /// `find_iter` order, with `template`. The template uses `re.sub`
+    /// syntax (`\1`, `\g<name>`, `\n`, ...); an invalid template or a
+    /// reference to a missing group is an error.
+    pub fn replacen(&self, input: &str, template: &str, limit: usize) -> Result<String, String> {
+        let template = Template::parse(template, self.program.n_groups, &self.group_names)?;
+        let mut matches = self.find_iter(input);
+        let mut out = String::new();
+        let mut last = 0;
+        let mut replaced = 0;
+        while limit == 0 || replaced < limit {
+            let Some(m) = matches.next() else { break };
+            out.extend(&matches.chars[last..m.start]);
+            template.expand(&matches.chars, &m, &mut out);
+            last = m.end;
+            replaced += 1;
+        }
+        out.extend(&matches.chars[last..]);
+        Ok(out)
+    }
+
+    /// Split `input` around the matches (like `re.split`, except that the
+    /// text of capturing groups is not included). Empty matches split too,
+    /// so `x*` splits "axb" into "", "a", "", "b", "".
+    pub fn split(&self, input: &str) -> Vec<String> {
+        let mut matches = self.find_iter(input);
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        while let Some(m) = matches.next() {
+            pieces.push(matches.chars[last..m.start].iter().collect());
+            last = m.end;
+        }
+        pieces.push(matches.chars[last..].iter().collect());
+        pieces
+    }
+
+    /// The leftmost match's groups (like `re.search(...).group(i)`).
+    pub fn captures(&self, input: &str) -> Option<Captures<'_>> {
+        let chars: Vec<char> = input.chars().collect();
+        let m = vm::search_from(&self.program, &chars, 0, true)?;
+        Some(Captures::new(&m, &chars, &self.group_names))
+    }
+
+    /// The groups of each match `find_iter` finds.
+    pub fn captures_iter(&self, input: &str) -> CapturesIter<'_> {
+        CapturesIter { matches: self.find_iter(input), names: &self.group_names }
+    }
+
+    /// Iterate over successive non-overlapping matches (like `re.finditer`).
+    ///
+    /// Each search resumes where the previous match ended, so `\G` anchors a
+    /// match to the end of the previous one. After an empty match the next
+    /// match may not be empty at that same position.
+    pub fn find_iter(&self, input: &str) -> Matches<'_> {
+        Matches {
+            program: &self.program,
+            chars: input.chars().collect(),
+            pos: 0,
+            allow_empty: true,
+            done: false,
+        }
+    }
+}
+
+/// Iterator returned by `Regex::find_iter`.
+pub struct Matches<'r> {
+    program: &'r Program,
+    chars: Vec<char>,
+    pos: usize,
+    allow_empty: bool,
+    done: bool,
+}
+
+impl Iterator for Matches<'_> {
+    type Item = MatchResult;
+
+    fn next(&mut self) -> Option<MatchResult> {
+        if self.done || self.pos > self.chars.len() {
+            return None;
+        }
+        match vm::search_from(self.program, &self.chars, self.pos, self.allow_empty) {
+            Some(m) => {
+                self.pos = m.end;
+                self.allow_empty = m.end > m.start;
+                Some(m)
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+/// A group's part of a match: char offsets into the input and its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// The groups of one match. A group that didn't take part in the match is
+/// `None`, while one that matched the empty string is an empty `Match`.
+#[derive(Debug, Clone)]
+pub struct Captures<'r> {
+    /// Indexed by group number; 0 is the whole match.
+    groups: Vec<Option<Match>>,
+    names: &'r [(String, usize)],
+}
+
+impl<'r> Captures<'r> {
+    fn new(m: &MatchResult, chars: &[char], names: &'r [(String, usize)]) -> Captures<'r> {
+        let groups = (0..m.captures.len() / 2)
+            .map(|i| {
+                m.group(i).map(|(start, end)| Match { start, end, text: chars[start..end].iter().collect() })
+            })
+            .collect();
+        Captures { groups, names }
+    }
+
+    /// Group `i` (0 is the whole match); `None` if it didn't take part or
+    /// the pattern has no such group.
+    pub fn get(&self, i: usize) -> Option<&Match> {
+        self.groups.get(i)?.as_ref()
+    }
+
+    /// The group named `name`; `None` if it didn't take part or no group
+    /// has that name.
+    pub fn name(&self, name: &str) -> Option<&Match> {
+        let &(_, i) = self.names.iter().find(|(n, _)| n == name)?;
+        self.get(i)
+    }
+
+    /// Every group in order, starting with the whole match.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&Match>> {
+        self.groups.iter().map(Option::as_ref)
+    }
+}
+
+/// Iterator returned by `Regex::captures_iter`.
+pub struct CapturesIter<'r> {
+    matches: Matches<'r>,
+    names: &'r [(String, usize)],
+}
+
+impl<'r> Iterator for CapturesIter<'r> {
+    type Item = Captures<'r>;
+
+    fn next(&mut self) -> Option<Captures<'r>> {
+        let m = self.matches.next()?;
+        Some(Captures::new(&m, &self.matches.chars, self.names))
+    }
+}
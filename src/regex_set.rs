@@ -0,0 +1,116 @@
+//! Matching many patterns against the same input in one pass.
+//!
+//! The patterns are compiled separately and concatenated into one program
+//! (`compiler::combine`) whose pattern ends are `MatchPattern(id)`. That
+//! program runs on the Pike VM, which scans the input once for all patterns.
+//! Patterns the Pike VM can't run (backreferences, lookaround, atomic groups,
+//! subroutine calls, counted loops, conditionals) are searched one by one
+//! with the backtracking VM instead.
+//!
+//! Before either runs, one Aho-Corasick pass over the input looks for the
+//! literals each pattern needs (from `literal::analyze`: a required
+//! substring, or else a set of prefixes or suffixes one of which must occur).
+//! Patterns whose literals are all absent are skipped, so a line usually
+//! only runs the few patterns it could plausibly match.
+
+use crate::aho_corasick::AhoCorasick;
+use crate::compiler::{self, Program};
+use crate::literal;
+use crate::parser::Parser;
+use crate::pike::{self, PikeVm};
+use crate::vm;
+
+/// A set of compiled regular expressions, numbered in the order given.
+pub struct RegexSet {
+    programs: Vec<Program>,
+    /// Combined program of the patterns the Pike VM supports.
+    pike: PikeVm,
+    /// Patterns searched with the backtracking VM.
+    fallback: Vec<usize>,
+    /// Every pattern's needed literals, and the pattern each belongs to.
+    literals: AhoCorasick,
+    owners: Vec<usize>,
+    /// Patterns with no needed literals, which always run.
+    unfiltered: Vec<bool>,
+}
+
+impl RegexSet {
+    /// Parse and compile every pattern. Errors name the failing pattern's
+    /// index: `pattern 3: unmatched ')'`.
+    pub fn new<I, S>(patterns: I) -> Result<RegexSet, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut programs = Vec::new();
+        let mut needles = Vec::new();
+        let mut owners = Vec::new();
+        let mut unfiltered = Vec::new();
+        for (i, pattern) in patterns.into_iter().enumerate() {
+            let mut parser = Parser::new(pattern.as_ref());
+            let ast = parser.parse().map_err(|e| format!("pattern {}: {}", i, e))?;
+            let program = compiler::compile(&ast, parser.group_count()).map_err(|e| format!("pattern {}: {}", i, e))?;
+            programs.push(program);
+            let lits = literal::analyze(&ast);
+            match lits.required.map(|r| vec![r]).or(lits.prefixes).or(lits.suffixes) {
+                Some(set) => {
+                    owners.extend(std::iter::repeat_n(i, set.len()));
+                    needles.extend(set);
+                    unfiltered.push(false);
+                }
+                None => unfiltered.push(true),
+            }
+        }
+        let (supported, fallback): (Vec<usize>, Vec<usize>) =
+            (0..programs.len()).partition(|&i| pike::supports(&programs[i].insts));
+        let multi = compiler::combine(supported.iter().map(|&i| (i, &programs[i])));
+        let pike = PikeVm::new(multi, programs.len());
+        let literals = AhoCorasick::new(&needles);
+        Ok(RegexSet { programs, pike, fallback, literals, owners, unfiltered })
+    }
+
+    /// Number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    /// Whether the set has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// Indices of the patterns matching somewhere in `input`, ascending.
+    pub fn matches(&self, input: &str) -> Vec<usize> {
+        let chars: Vec<char> = input.chars().collect();
+        let found = self.run(&chars, true);
+        (0..found.len()).filter(|&i| found[i].is_some()).collect()
+    }
+
+    /// Whether any pattern matches somewhere in `input`.
+    pub fn is_match(&self, input: &str) -> bool {
+        !self.matches(input).is_empty()
+    }
+
+    /// The leftmost match `(start, end)` of each pattern, indexed like the
+    /// patterns: the same spans `Regex::search` gives for each on its own.
+    pub fn find_each(&self, input: &str) -> Vec<Option<(usize, usize)>> {
+        let chars: Vec<char> = input.chars().collect();
+        self.run(&chars, false)
+    }
+
+    fn run(&self, chars: &[char], earliest: bool) -> Vec<Option<(usize, usize)>> {
+        let mut active = self.unfiltered.clone();
+        if !self.owners.is_empty() {
+            for (lit, present) in self.literals.occurring(chars).into_iter().enumerate() {
+                if present {
+                    active[self.owners[lit]] = true;
+                }
+            }
+        }
+        let mut found = self.pike.find_each(chars, &active, earliest);
+        for &i in self.fallback.iter().filter(|&&i| active[i]) {
+            found[i] = vm::search_from(&self.programs[i], chars, 0, true).map(|m| (m.start, m.end));
+        }
+        found
+    }
+}
@@ -0,0 +1,145 @@
+//! Replacement templates for `Regex::replace` and friends.
+//!
+//! The syntax is Python's `re.sub`: `\1`..`\99` and `\g<1>` / `\g<name>`
+//! insert a group (empty if it didn't participate), `\n`, `\t`, `\r`, `\f`,
+//! `\v`, `\a`, `\b` and `\\` are the usual escapes, `\0` and three-digit
+//! octal escapes like `\101` insert a character, and a backslash before any
+//! other non-letter is kept as is. A backslash before another ASCII letter is
+//! an error.
+
+use crate::vm::MatchResult;
+
+enum Piece {
+    Literal(String),
+    Group(usize),
+}
+
+/// A parsed replacement template.
+pub(crate) struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Parse `template` for a pattern with `n_groups` groups and the given
+    /// named groups.
+    pub(crate) fn parse(template: &str, n_groups: usize, names: &[(String, usize)]) -> Result<Template, String> {
+        let chars: Vec<char> = template.chars().collect();
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let group = |literal: &mut String, pieces: &mut Vec<Piece>, index: usize| -> Result<(), String> {
+            if index > n_groups {
+                return Err(format!("invalid group reference {}", index));
+            }
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(std::mem::take(literal)));
+            }
+            pieces.push(Piece::Group(index));
+            Ok(())
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            i += 1;
+            if c != '\\' {
+                literal.push(c);
+                continue;
+            }
+            let Some(&e) = chars.get(i) else {
+                return Err("bad escape (end of pattern)".to_string());
+            };
+            i += 1;
+            let octal = |c: Option<&char>| c.is_some_and(|c| ('0'..='7').contains(c));
+            match e {
+                'g' => {
+                    if chars.get(i) != Some(&'<') {
+                        return Err("missing <".to_string());
+                    }
+                    let Some(len) = chars[i + 1..].iter().position(|&c| c == '>') else {
+                        return Err("missing >, unterminated name".to_string());
+                    };
+                    let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                    i += len + 2;
+                    let index = if name.is_empty() {
+                        return Err("missing group name".to_string());
+                    } else if name.chars().all(|c| c.is_ascii_digit()) {
+                        name.parse().unwrap_or(usize::MAX)
+                    } else if name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    {
+                        match names.iter().find(|(n, _)| *n == name) {
+                            Some(&(_, index)) => index,
+                            None => return Err(format!("unknown group name '{}'", name)),
+                        }
+                    } else {
+                        return Err(format!("bad character in group name '{}'", name));
+                    };
+                    group(&mut literal, &mut pieces, index)?;
+                }
+                '0' => {
+                    // `\0` takes up to two more octal digits.
+                    let mut value = 0;
+                    for _ in 0..2 {
+                        if !octal(chars.get(i)) {
+                            break;
+                        }
+                        value = value * 8 + chars[i].to_digit(8).unwrap();
+                        i += 1;
+                    }
+                    literal.push(char::from_u32(value).unwrap());
+                }
+                '1'..='9' => {
+                    // Three octal digits are an octal escape; otherwise one or
+                    // two digits are a group number.
+                    let Some(&d) = chars.get(i).filter(|c| c.is_ascii_digit()) else {
+                        group(&mut literal, &mut pieces, e.to_digit(10).unwrap() as usize)?;
+                        continue;
+                    };
+                    i += 1;
+                    if octal(Some(&e)) && octal(Some(&d)) && octal(chars.get(i)) {
+                        let digits: String = [e, d, chars[i]].iter().collect();
+                        i += 1;
+                        let value = u32::from_str_radix(&digits, 8).unwrap();
+                        if value > 0o377 {
+                            return Err(format!("octal escape value \\{} outside of range 0-0o377", digits));
+                        }
+                        literal.push(char::from_u32(value).unwrap());
+                    } else {
+                        let index = e.to_digit(10).unwrap() * 10 + d.to_digit(10).unwrap();
+                        group(&mut literal, &mut pieces, index as usize)?;
+                    }
+                }
+                'n' => literal.push('\n'),
+                't' => literal.push('\t'),
+                'r' => literal.push('\r'),
+                'f' => literal.push('\x0c'),
+                'v' => literal.push('\x0b'),
+                'a' => literal.push('\x07'),
+                'b' => literal.push('\x08'),
+                '\\' => literal.push('\\'),
+                _ if e.is_ascii_alphabetic() => return Err(format!("bad escape \\{}", e)),
+                _ => {
+                    literal.push('\\');
+                    literal.push(e);
+                }
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Template { pieces })
+    }
+
+    /// Append the replacement for `m`, a match in `chars`, to `out`.
+    pub(crate) fn expand(&self, chars: &[char], m: &MatchResult, out: &mut String) {
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => out.push_str(text),
+                Piece::Group(i) => {
+                    if let Some((s, e)) = m.group(*i) {
+                        out.extend(&chars[s..e]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! AST simplification, run by `compiler::compile` before emitting code.
+//!
+//! Every rewrite keeps the meaning of the pattern, including leftmost-first
+//! priority between alternatives and group numbering:
+//! - `(?:x)` becomes `x`; one-element concatenations and alternations
+//!   become their element;
+//! - nested concatenations and alternations are flattened;
+//! - adjacent alternatives with a common literal prefix are factored:
+//!   `abc|abd` becomes `ab(?:c|d)`;
+//! - an alternation of single characters becomes a class: `c|d` is `[cd]`;
+//! - `(?i:...)` around nothing case-sensitive is dropped.
+
+use crate::ast::*;
+
+/// Simplify `node`.
+pub fn simplify(node: AstNode) -> AstNode {
+    match node {
+        AstNode::NonCapturingGroup { node } => simplify(*node),
+        AstNode::Concat(nodes) => {
+            let mut out = Vec::with_capacity(nodes.len());
+            for n in nodes {
+                match simplify(n) {
+                    AstNode::Concat(inner) => out.extend(inner),
+                    n => out.push(n),
+                }
+            }
+            from_seq(out)
+        }
+        AstNode::Alternation(branches) => {
+            let mut out = Vec::with_capacity(branches.len());
+            for b in branches {
+                match simplify(b) {
+                    AstNode::Alternation(inner) => out.extend(inner),
+                    b => out.push(b),
+                }
+            }
+            alternation(factor_prefixes(out))
+        }
+        AstNode::Quantifier { node, kind, greedy } => {
+            AstNode::Quantifier { node: Box::new(simplify(*node)), kind, greedy }
+        }
+        AstNode::Group { index, node } => AstNode::Group { index, node: Box::new(simplify(*node)) },
+        AstNode::AtomicGroup { node } => AstNode::AtomicGroup { node: Box::new(simplify(*node)) },
+        AstNode::Lookahead { node, positive } => {
+            AstNode::Lookahead { node: Box::new(simplify(*node)), positive }
+        }
+        AstNode::Lookbehind { node, positive } => {
+            AstNode::Lookbehind { node: Box::new(simplify(*node)), positive }
+        }
+        AstNode::CaseInsensitive { node } => {
+            let node = simplify(*node);
+            if is_case_sensitive(&node) {
+                AstNode::CaseInsensitive { node: Box::new(node) }
+            } else {
+                node
+            }
+        }
+        AstNode::Conditional { group, yes, no } => AstNode::Conditional {
+            group,
+            yes: Box::new(simplify(*yes)),
+            no: no.map(|n| Box::new(simplify(*n))),
+        },
+        other => other,
+    }
+}
+
+/// Build an alternation from simplified branches.
+fn alternation(mut branches: Vec<AstNode>) -> AstNode {
+    if branches.len() == 1 {
+        return branches.pop().unwrap();
+    }
+    // Single characters are mutually exclusive, so their order doesn't matter.
+    let chars: Option<Vec<ClassItem>> = branches
+        .iter()
+        .map(|b| match b {
+            AstNode::Literal(c) => Some(ClassItem::Literal(*c)),
+            _ => None,
+        })
+        .collect();
+    match chars {
+        Some(ranges) => AstNode::CharClass { ranges, negated: false },
+        None => AstNode::Alternation(branches),
+    }
+}
+
+/// Factor the common literal prefix out of each run of adjacent branches
+/// starting with the same literal. Only adjacent branches are grouped, so
+/// the order in which alternatives are tried is unchanged.
+fn factor_prefixes(branches: Vec<AstNode>) -> Vec<AstNode> {
+    let seqs: Vec<Vec<AstNode>> = branches.into_iter().map(into_seq).collect();
+    let mut out = Vec::with_capacity(seqs.len());
+    let mut i = 0;
+    while i < seqs.len() {
+        let first = leading_literal(&seqs[i]);
+        let mut j = i + 1;
+        while first.is_some() && j < seqs.len() && leading_literal(&seqs[j]) == first {
+            j += 1;
+        }
+        if j - i < 2 {
+            out.push(from_seq(seqs[i].clone()));
+            i += 1;
+            continue;
+        }
+        let run = &seqs[i..j];
+        let mut prefix_len = 1;
+        while run.iter().all(|s| {
+            matches!(s.get(prefix_len), Some(AstNode::Literal(c)) if Some(c) == literal_at(&run[0], prefix_len))
+        }) {
+            prefix_len += 1;
+        }
+        let mut seq = run[0][..prefix_len].to_vec();
+        let rests = run.iter().map(|s| from_seq(s[prefix_len..].to_vec())).collect();
+        match alternation(factor_prefixes(rests)) {
+            AstNode::Concat(inner) => seq.extend(inner),
+            rest => seq.push(rest),
+        }
+        out.push(from_seq(seq));
+        i = j;
+    }
+    out
+}
+
+fn leading_literal(seq: &[AstNode]) -> Option<char> {
+    literal_at(seq, 0).copied()
+}
+
+fn literal_at(seq: &[AstNode], i: usize) -> Option<&char> {
+    match seq.get(i) {
+        Some(AstNode::Literal(c)) => Some(c),
+        _ => None,
+    }
+}
+
+/// A node as a sequence of concatenated nodes.
+fn into_seq(node: AstNode) -> Vec<AstNode> {
+    match node {
+        AstNode::Concat(nodes) => nodes,
+        node => vec![node],
+    }
+}
+
+/// The inverse of `into_seq`.
+fn from_seq(mut seq: Vec<AstNode>) -> AstNode {
+    if seq.len() == 1 { seq.pop().unwrap() } else { AstNode::Concat(seq) }
+}
+
+/// Whether case-insensitive matching could change what `node` matches.
+fn is_case_sensitive(node: &AstNode) -> bool {
+    match node {
+        AstNode::Literal(c) => c.is_ascii_alphabetic(),
+        AstNode::CharClass { .. } | AstNode::Backreference(_) => true,
+        AstNode::Dot | AstNode::ShorthandClass(_) | AstNode::Anchor(_) | AstNode::Subroutine(_) => false,
+        AstNode::Concat(nodes) | AstNode::Alternation(nodes) => nodes.iter().any(is_case_sensitive),
+        AstNode::Quantifier { node, .. }
+        | AstNode::Group { node, .. }
+        | AstNode::NonCapturingGroup { node }
+        | AstNode::AtomicGroup { node }
+        | AstNode::Lookahead { node, .. }
+        | AstNode::Lookbehind { node, .. }
+        | AstNode::CaseInsensitive { node } => is_case_sensitive(node),
+        AstNode::Conditional { yes, no, .. } => {
+            is_case_sensitive(yes) || no.as_deref().is_some_and(is_case_sensitive)
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Searching input that arrives in pieces.
+//!
+//! `StreamSearcher` buffers the text fed to it and reports each match as
+//! soon as no further input could change it, with absolute char offsets.
+//! The matches are the ones `Regex::find_iter` finds in the whole input. A
+//! match attempt that reads or tests the end of the buffer (`a+` on "aaa",
+//! `\b` or `$` at the end) waits for more input or `finish`.
+//!
+//! Text before the next position a match could start at is dropped once no
+//! lookbehind can reach it (`Program::lookbehind_reach`, plus one character
+//! for `\b`), so buffering stays bounded while matches keep resolving.
+//! Patterns with unbounded lookbehind keep everything, and a pending
+//! attempt (`a.*b` with no `b` yet) holds the buffer from where it started.
+
+use crate::compiler::Program;
+use crate::vm::{self, MatchResult, Partial};
+use std::io::{self, Read};
+
+/// Size of the reads done by `search_reader`.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Incremental search over text fed in chunks (see `Regex::stream`).
+pub struct StreamSearcher<'r> {
+    program: &'r Program,
+    buf: Vec<char>,
+    /// Absolute offset of `buf[0]`.
+    offset: usize,
+    /// Where the current search began (the end of the previous match);
+    /// `\G` matches here.
+    start: usize,
+    /// Starts before this are known not to match.
+    resume: usize,
+    /// Whether an empty match at `start` is allowed (not right after an
+    /// empty match).
+    allow_empty: bool,
+    done: bool,
+}
+
+impl<'r> StreamSearcher<'r> {
+    pub(crate) fn new(program: &'r Program) -> Self {
+        StreamSearcher { program, buf: Vec::new(), offset: 0, start: 0, resume: 0, allow_empty: true, done: false }
+    }
+
+    /// Append `chunk` and return the matches it settled.
+    pub fn feed(&mut self, chunk: &str) -> Vec<MatchResult> {
+        self.buf.extend(chunk.chars());
+        let matches = self.run(false);
+        self.trim();
+        matches
+    }
+
+    /// Signal the end of the input and return the remaining matches.
+    pub fn finish(mut self) -> Vec<MatchResult> {
+        self.run(true)
+    }
+
+    /// Report matches until one depends on input not seen yet.
+    fn run(&mut self, at_end: bool) -> Vec<MatchResult> {
+        let mut matches = Vec::new();
+        while !self.done {
+            // `\G` can't match once the search has moved past where it began.
+            let start = (self.start >= self.offset).then(|| self.start - self.offset);
+            let from = self.resume - self.offset;
+            match vm::search_partial(self.program, &self.buf, start, from, self.allow_empty, at_end) {
+                Partial::Match(mut m) => {
+                    m.start += self.offset;
+                    m.end += self.offset;
+                    for slot in m.captures.iter_mut().flatten() {
+                        *slot += self.offset;
+                    }
+                    self.start = m.end;
+                    self.resume = m.end;
+                    self.allow_empty = m.end > m.start;
+                    matches.push(m);
+                }
+                Partial::NeedMore { resume } => {
+                    self.resume = self.offset + resume;
+                    break;
+                }
+                Partial::Done => self.done = true,
+            }
+        }
+        matches
+    }
+
+    /// Drop buffered text no later search can look at.
+    fn trim(&mut self) {
+        let Some(reach) = self.program.lookbehind_reach else { return };
+        let needed = self.resume.min(self.offset + self.buf.len());
+        let keep_from = needed.saturating_sub(reach + 1);
+        if keep_from > self.offset {
+            self.buf.drain(..keep_from - self.offset);
+            self.offset = keep_from;
+        }
+    }
+}
+
+/// Read UTF-8 text from `reader` in chunks and call `on_match` for each
+/// match, as `StreamSearcher` finds them.
+pub(crate) fn search_reader<R: Read>(
+    program: &Program,
+    mut reader: R,
+    mut on_match: impl FnMut(MatchResult),
+) -> io::Result<()> {
+    let mut searcher = StreamSearcher::new(program);
+    let mut chunk = vec![0u8; READ_CHUNK];
+    // Bytes read but not yet fed: at most a character split across reads.
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        pending.extend_from_slice(&chunk[..n]);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            // An incomplete character at the end is finished by the next read.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&pending[..valid]).unwrap();
+        searcher.feed(text).into_iter().for_each(&mut on_match);
+        pending.drain(..valid);
+    }
+    if !pending.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "input ends inside a UTF-8 character"));
+    }
+    searcher.finish().into_iter().for_each(on_match);
+    Ok(())
+}
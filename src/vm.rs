@@ -1,16 +1,21 @@
-/// VM executor: runs compiled bytecode against an input string.
-/// Uses recursive backtracking to support backreferences and lookaround.
-///
-/// Performance optimizations:
-/// - Undo log instead of full captures.clone() on Split (save/restore only changed slots)
-/// - Recursion depth limit to prevent stack overflow on pathological inputs
+//! VM executor: runs compiled bytecode against an input string.
+//! Uses recursive backtracking to support backreferences and lookaround.
+//!
+//! Performance optimizations:
+//! - Undo log instead of full captures.clone() on Split (save/restore only changed slots)
+//! - Recursion depth limit to prevent stack overflow on pathological inputs
 
-use crate::ast::{ClassItem, ShorthandKind};
-use crate::compiler::{Inst, Program};
+use crate::ast::ShorthandKind;
+use crate::compiler::{Inst, MultiProgram, Program};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 /// Maximum recursion depth for the backtracking VM.
 const MAX_DEPTH: usize = 10_000;
 
+/// Maximum nesting of subroutine calls (`(?R)`, `(?1)`, `(?&name)`).
+const MAX_CALL_DEPTH: usize = 1_000;
+
 /// Result of a match attempt.
 pub struct MatchResult {
     /// Start position in the input.
@@ -22,68 +27,513 @@ pub struct MatchResult {
     pub captures: Vec<Option<usize>>,
 }
 
+impl MatchResult {
+    /// The span of group `i` (0 is the whole match), or `None` if the group
+    /// didn't take part in the match (an empty group is `Some((p, p))`).
+    pub fn group(&self, i: usize) -> Option<(usize, usize)> {
+        let start = self.captures.get(i * 2).copied().flatten()?;
+        let end = self.captures.get(i * 2 + 1).copied().flatten()?;
+        Some((start, end))
+    }
+}
+
+/// An execution event reported by `search_traced`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A match attempt starts at `pos`. Starts the prefilters rule out get
+    /// no attempt.
+    Attempt { pos: usize },
+    /// The VM runs the instruction at `pc` with the input at `pos`.
+    Step { pc: usize, pos: usize },
+    /// The branch entered at `pc` with the input at `pos` failed, so the VM
+    /// backtracks and takes the other alternative from `pos`.
+    Backtrack { pc: usize, pos: usize },
+}
+
+/// Receives the events of a traced search.
+type Tracer<'a> = dyn Fn(TraceEvent) + 'a;
+
 /// An entry in the undo log: (slot_index, old_value).
 type UndoEntry = (usize, Option<usize>);
 
+/// An active subroutine call. Frames form an immutable linked list so each
+/// backtracking branch can hold its own call stack and share the common tail.
+struct CallFrame {
+    /// Group whose `Return` pops this frame (0 = whole pattern).
+    group: usize,
+    /// Instruction to resume at after the call.
+    return_pc: usize,
+    /// Caller's capture slots, restored on return (per-call capture semantics).
+    saved_captures: Vec<Option<usize>>,
+    /// Caller's case-insensitive depth.
+    ci_depth: usize,
+    /// Number of frames in the stack, including this one.
+    depth: usize,
+    parent: CallStack,
+}
+
+/// The subroutine call stack; `None` when not inside any call.
+type CallStack = Option<Rc<CallFrame>>;
+
+/// Per-search state shared by every `exec` call in one match attempt.
+#[derive(Clone, Copy)]
+struct Ctx<'a> {
+    insts: &'a [Inst],
+    chars: &'a [char],
+    /// Position where the current search began; `\G` asserts `pos == search_start`.
+    search_start: usize,
+    /// When false, a top-level `Match` that would end where it started is
+    /// rejected. Used by iteration to step past an empty match.
+    allow_empty: bool,
+    /// When set, a top-level `Match` only counts at this position (the VM
+    /// backtracks otherwise). Used by `fullmatch`.
+    end_at: Option<usize>,
+    /// Forward reads stop here (the end of a search range); assertions and
+    /// lookaheads still see the whole input.
+    limit: usize,
+    /// Set when the search reads or tests the end of `chars`, so its outcome
+    /// could change if more input followed (see `search_partial`).
+    hit_end: Option<&'a Cell<bool>>,
+    /// Receives execution events (see `search_traced`).
+    trace: Option<&'a Tracer<'a>>,
+    /// Whether the code being run matches right to left (a reversed program
+    /// or lookbehind body): consuming instructions read `chars[pos - 1]`.
+    reverse: bool,
+}
+
+impl Ctx<'_> {
+    /// The same search, reading in the given direction.
+    fn direction(&self, reverse: bool) -> Self {
+        Ctx { reverse, ..*self }
+    }
+
+    /// Note that the outcome depends on what follows `chars`.
+    fn touch_end(&self) {
+        if let Some(hit) = self.hit_end {
+            hit.set(true);
+        }
+    }
+
+    /// Report `event` if the search is traced.
+    #[inline]
+    fn emit(&self, event: TraceEvent) {
+        if let Some(trace) = self.trace {
+            trace(event);
+        }
+    }
+
+    /// The context for a lookahead body, which may read past the range end.
+    fn lookahead(&self) -> Self {
+        Ctx { reverse: false, limit: self.chars.len(), ..*self }
+    }
+}
+
 /// Try to find a match anywhere in the input (like `re.search`).
 pub fn search(program: &Program, input: &str) -> Option<MatchResult> {
     let chars: Vec<char> = input.chars().collect();
-    let n_slots = (program.n_groups + 1) * 2;
-
-    // If anchored at start, only try position 0
-    if program.anchored_start {
-        let mut captures = vec![None; n_slots];
-        captures[0] = Some(0);
-        let mut undo_log = Vec::new();
-        if exec(program, &chars, 0, 0, &mut captures, &mut undo_log, 0, 0) {
-            captures[1] = Some(captures[1].unwrap_or(0));
-            let end = captures[1].unwrap();
-            return Some(MatchResult {
-                start: 0,
-                end,
-                captures,
-            });
+    search_from(program, &chars, 0, true)
+}
+
+/// Find the leftmost match starting at or after `start`. Assertions still see
+/// the whole input, and `\G` matches only at `start`.
+///
+/// If `allow_empty_at_start` is false, an empty match at `start` itself is not
+/// accepted (the VM backtracks looking for a non-empty one instead), which is
+/// how iteration advances past an empty match the way Python's `finditer` does.
+pub fn search_from(
+    program: &Program,
+    chars: &[char],
+    start: usize,
+    allow_empty_at_start: bool,
+) -> Option<MatchResult> {
+    search_within(program, chars, start, chars.len(), allow_empty_at_start, None)
+}
+
+/// `search_from(program, chars, start, true)`, calling `on_event` for each
+/// match attempt, VM step and backtrack, in order. For seeing why a pattern
+/// is slow or matches unexpectedly; a pure literal alternation runs on its
+/// automaton and reports nothing.
+pub fn search_traced(
+    program: &Program,
+    chars: &[char],
+    start: usize,
+    on_event: impl FnMut(TraceEvent),
+) -> Option<MatchResult> {
+    let on_event = RefCell::new(on_event);
+    let tracer = |event| (on_event.borrow_mut())(event);
+    search_within(program, chars, start, chars.len(), true, Some(&tracer))
+}
+
+/// How one edge of a search range treats the text beyond it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The text beyond the edge is context: `\b` and lookarounds see it, and
+    /// `^`/`$` match only at the real ends of the input.
+    Context,
+    /// The input ends at the edge, as if it were sliced there: `^`/`$` match
+    /// at it and nothing beyond it is visible.
+    Slice,
+}
+
+/// Edge semantics for `search_range`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeOptions {
+    pub start: Edge,
+    pub end: Edge,
+}
+
+impl RangeOptions {
+    /// Both edges are context.
+    pub const CONTEXT: RangeOptions = RangeOptions { start: Edge::Context, end: Edge::Context };
+    /// Both edges slice: the same as searching the substring.
+    pub const SLICE: RangeOptions = RangeOptions { start: Edge::Slice, end: Edge::Slice };
+    /// Python's `pos`/`endpos`: text before `pos` is context, and the input
+    /// ends at `endpos`.
+    pub const PYTHON: RangeOptions = RangeOptions { start: Edge::Context, end: Edge::Slice };
+}
+
+impl Default for RangeOptions {
+    fn default() -> Self {
+        RangeOptions::CONTEXT
+    }
+}
+
+/// Find the leftmost match lying within `chars[start..end]`, with the range
+/// edges treated as `options` says. `\G` matches at `start`. Positions in
+/// the result are offsets into `chars`.
+pub fn search_range(
+    program: &Program,
+    chars: &[char],
+    start: usize,
+    end: usize,
+    options: RangeOptions,
+) -> Option<MatchResult> {
+    if start > end || end > chars.len() {
+        return None;
+    }
+    // A sliced edge cuts the input; positions are shifted back afterwards.
+    let lo = if options.start == Edge::Slice { start } else { 0 };
+    let hi = if options.end == Edge::Slice { end } else { chars.len() };
+    let mut m = search_within(program, &chars[lo..hi], start - lo, end - lo, true, None)?;
+    m.start += lo;
+    m.end += lo;
+    for slot in m.captures.iter_mut().flatten() {
+        *slot += lo;
+    }
+    Some(m)
+}
+
+/// `search_from`, with matches confined to end at or before `limit`, and
+/// execution events sent to `trace` if given.
+fn search_within(
+    program: &Program,
+    chars: &[char],
+    start: usize,
+    limit: usize,
+    allow_empty_at_start: bool,
+    trace: Option<&Tracer>,
+) -> Option<MatchResult> {
+    // A pure literal alternation never matches empty and has no groups or
+    // assertions, so the automaton's leftmost-first match is the answer.
+    if let Some(ac) = &program.literal_matcher {
+        let (s, e) = ac.find(&chars[..limit], start)?;
+        return Some(MatchResult { start: s, end: e, captures: vec![Some(s), Some(e)] });
+    }
+
+    let mut ctx = Ctx {
+        insts: &program.insts,
+        chars,
+        search_start: start,
+        allow_empty: allow_empty_at_start,
+        end_at: None,
+        limit,
+        hit_end: None,
+        trace,
+        reverse: false,
+    };
+
+    // If anchored at the start of the text, only position 0 can match;
+    // if anchored with `\G`, only the search start can.
+    let last = if program.anchored_start {
+        if start > 0 {
+            return None;
         }
+        0
+    } else if program.anchored_search {
+        start
+    } else {
+        limit
+    };
+
+    // Every match lies at or after `start`, so a missing required literal
+    // means there is nothing to find.
+    if let Some(required) = &program.required
+        && required.find(chars, start).is_none()
+    {
         return None;
     }
 
+    // An end-anchored pattern must match a suffix of the input: reject
+    // inputs lacking its literal suffix, and skip starts too far from the end.
+    let mut first = start;
+    if program.anchored_end {
+        if let Some(suffixes) = &program.end_suffixes
+            && !suffixes.iter().any(|s| chars.ends_with(s))
+        {
+            return None;
+        }
+        if let Some(max) = program.max_len {
+            first = first.max(chars.len().saturating_sub(max));
+        }
+        // Unbounded length: one backward run from the end tells whether any
+        // match exists before trying starts one by one.
+        if let Some(rev) = &program.reverse_program {
+            // Not traced: its instruction addresses are the reversed program's.
+            let rev_ctx = Ctx { insts: &rev.insts, reverse: true, allow_empty: true, trace: None, ..ctx };
+            exec_at(&rev_ctx, rev, chars.len())?;
+        }
+    }
+
     // Try at each starting position
-    for start in 0..=chars.len() {
-        // First-char optimization: skip positions where the first required char doesn't match
-        if let Some(fc) = program.first_char {
-            if start < chars.len() {
-                if chars[start] != fc {
+    let mut pos = first;
+    while pos <= last {
+        // Literal prefilter: jump straight to the next position where a
+        // literal prefix of the pattern occurs.
+        if let Some(prefilter) = &program.prefilter {
+            match prefilter.next_candidate(chars, pos) {
+                Some(p) if p <= last => pos = p,
+                _ => return None,
+            }
+        }
+
+        // First-char optimization: skip positions whose character can't start a match
+        if let Some(set) = &program.first_set {
+            if pos < limit {
+                if !set.contains(chars[pos]) {
+                    pos += 1;
                     continue;
                 }
             } else {
-                // At end of input, a required first char can't match
-                continue;
+                // At end of input, a non-empty match can't start
+                break;
             }
         }
 
-        let mut captures = vec![None; n_slots];
-        captures[0] = Some(start);
-        let mut undo_log = Vec::new();
-        if exec(program, &chars, start, 0, &mut captures, &mut undo_log, 0, 0) {
-            captures[1] = Some(captures[1].unwrap_or(start));
-            let end = captures[1].unwrap();
-            return Some(MatchResult {
-                start,
-                end,
-                captures,
-            });
+        ctx.allow_empty = allow_empty_at_start || pos != start;
+        ctx.emit(TraceEvent::Attempt { pos });
+        if let Some(result) = exec_at(&ctx, program, pos) {
+            return Some(result);
         }
+        pos += 1;
     }
     None
 }
 
+/// Match anchored at `start` (like `re.match` with `pos`). With `end_at`,
+/// the match must also end there; `fullmatch` passes the input length.
+/// Assertions and lookarounds still see the whole input, and `\G` matches
+/// at `start`.
+pub fn match_at(program: &Program, chars: &[char], start: usize, end_at: Option<usize>) -> Option<MatchResult> {
+    if start > chars.len() || (program.anchored_start && start > 0) {
+        return None;
+    }
+    let ctx = Ctx {
+        insts: &program.insts,
+        chars,
+        search_start: start,
+        allow_empty: true,
+        end_at,
+        limit: chars.len(),
+        hit_end: None,
+        trace: None,
+        reverse: false,
+    };
+    exec_at(&ctx, program, start)
+}
+
+/// Outcome of `search_partial`.
+pub(crate) enum Partial {
+    /// A match that no further input can change.
+    Match(MatchResult),
+    /// No match starts before `resume`; what happens from there depends on
+    /// input not seen yet.
+    NeedMore { resume: usize },
+    /// No match at or after `from`, whatever follows.
+    Done,
+}
+
+/// Leftmost match at or after `from` when `chars` may be only the beginning
+/// of the input (unless `at_end`). Each start position is tried as in
+/// `search_from`, noting whether the attempt read or tested the end of
+/// `chars`; the first one that did decides nothing until more input arrives.
+///
+/// `start` is where the search began (`\G` matches there; `None` once it
+/// lies before `from`), and an empty match there counts only if
+/// `allow_empty_at_start`. The literal prefilters and end-anchor checks
+/// assume the whole input is present, so only the first-character set is
+/// used to skip positions.
+pub(crate) fn search_partial(
+    program: &Program,
+    chars: &[char],
+    start: Option<usize>,
+    from: usize,
+    allow_empty_at_start: bool,
+    at_end: bool,
+) -> Partial {
+    let hit = Cell::new(false);
+    let mut ctx = Ctx {
+        insts: &program.insts,
+        chars,
+        search_start: start.unwrap_or(usize::MAX),
+        allow_empty: allow_empty_at_start,
+        end_at: None,
+        limit: chars.len(),
+        hit_end: (!at_end).then_some(&hit),
+        trace: None,
+        reverse: false,
+    };
+    let last = if program.anchored_start {
+        if from > 0 {
+            return Partial::Done;
+        }
+        0
+    } else if program.anchored_search {
+        match start {
+            Some(s) if s >= from => s,
+            _ => return Partial::Done,
+        }
+    } else {
+        chars.len()
+    };
+    for pos in from..=last {
+        if let Some(set) = &program.first_set {
+            match chars.get(pos) {
+                Some(&c) if !set.contains(c) => continue,
+                Some(_) => {}
+                // A non-empty match can't start at the end of the input.
+                None if at_end => break,
+                None => return Partial::NeedMore { resume: pos },
+            }
+        }
+        ctx.allow_empty = allow_empty_at_start || Some(pos) != start;
+        let result = exec_at(&ctx, program, pos);
+        if hit.get() {
+            return Partial::NeedMore { resume: pos };
+        }
+        if let Some(m) = result {
+            return Partial::Match(m);
+        }
+    }
+    if at_end || program.anchored_start || program.anchored_search {
+        Partial::Done
+    } else {
+        Partial::NeedMore { resume: chars.len() + 1 }
+    }
+}
+
+/// Find the match ending at the rightmost position at or before `end`, using
+/// a program from `compiler::compile_reverse`. Each candidate end is tried
+/// by running the program right to left; the match starts where it stops.
+/// `\G` matches at `end`.
+pub fn search_reverse(program: &Program, chars: &[char], end: usize) -> Option<MatchResult> {
+    let ctx = Ctx {
+        insts: &program.insts,
+        chars,
+        search_start: end,
+        allow_empty: true,
+        end_at: None,
+        limit: chars.len(),
+        hit_end: None,
+        trace: None,
+        reverse: program.reverse,
+    };
+    (0..=end.min(chars.len())).rev().find_map(|pos| exec_at(&ctx, program, pos))
+}
+
+/// Run the pattern entered at `entry` in a combined program, anchored at
+/// `start`, and return where its match ends. `\G` matches at `start`; an
+/// empty match counts only if `allow_empty`.
+pub fn exec_entry(multi: &MultiProgram, chars: &[char], entry: usize, start: usize, allow_empty: bool) -> Option<usize> {
+    let ctx = Ctx {
+        insts: &multi.insts,
+        chars,
+        search_start: start,
+        allow_empty,
+        end_at: None,
+        limit: chars.len(),
+        hit_end: None,
+        trace: None,
+        reverse: false,
+    };
+    let mut captures = vec![None; multi.n_slots];
+    captures[0] = Some(start);
+    let matched = exec(&ctx, start, entry, &mut captures, &mut Vec::new(), 0, 0, None);
+    matched.then(|| captures[1].unwrap_or(start))
+}
+
+/// Run the whole program anchored at `start` (in a reversed program, the
+/// position the match ends at). `ctx` holds `program`'s instructions.
+fn exec_at(ctx: &Ctx, program: &Program, start: usize) -> Option<MatchResult> {
+    let n_group_slots = (program.n_groups + 1) * 2;
+    let mut captures = vec![None; n_group_slots + program.n_counters];
+    captures[0] = Some(start);
+    let mut undo_log = Vec::new();
+    if exec(ctx, start, 0, &mut captures, &mut undo_log, 0, 0, None) {
+        // Drop the counter registers; callers only see group slots.
+        captures.truncate(n_group_slots);
+        let stop = captures[1].unwrap_or(start);
+        let (start, end) = if ctx.reverse { (stop, start) } else { (start, stop) };
+        captures[0] = Some(start);
+        captures[1] = Some(end);
+        return Some(MatchResult {
+            start,
+            end,
+            captures,
+        });
+    }
+    None
+}
+
+/// The character a one-character instruction consumes at `pos` and the
+/// position after it, reading backward in reversed code.
+fn next_char(ctx: &Ctx, pos: usize) -> Option<(char, usize)> {
+    if ctx.reverse {
+        pos.checked_sub(1).map(|p| (ctx.chars[p], p))
+    } else {
+        if pos >= ctx.chars.len() {
+            ctx.touch_end();
+        }
+        ctx.chars[..ctx.limit].get(pos).map(|&c| (c, pos + 1))
+    }
+}
+
+/// Match `text` at `pos` (the text ending at `pos` in reversed code) and
+/// return the position after it.
+fn match_text(ctx: &Ctx, pos: usize, text: &[char], case_insensitive: bool) -> Option<usize> {
+    let n = text.len();
+    let at = if ctx.reverse { pos.checked_sub(n)? } else { pos };
+    if !ctx.reverse && at + n > ctx.chars.len() {
+        ctx.touch_end();
+    }
+    let candidate = ctx.chars[..ctx.limit].get(at..at + n)?;
+    let equal = if case_insensitive {
+        candidate.iter().zip(text).all(|(a, b)| char_eq(*a, *b, true))
+    } else {
+        candidate == text
+    };
+    if !equal {
+        return None;
+    }
+    Some(if ctx.reverse { at } else { at + n })
+}
+
 /// Compare two characters, optionally case-insensitively.
-fn char_eq(a: char, b: char, case_insensitive: bool) -> bool {
+pub(crate) fn char_eq(a: char, b: char, case_insensitive: bool) -> bool {
     if a == b {
         return true;
     }
     if case_insensitive {
-        a.to_ascii_lowercase() == b.to_ascii_lowercase()
+        a.eq_ignore_ascii_case(&b)
     } else {
         false
     }
@@ -96,86 +546,116 @@ fn char_eq(a: char, b: char, case_insensitive: bool) -> bool {
 /// avoiding full Vec clones on every Split instruction.
 ///
 /// `ci_depth` tracks nested case-insensitive regions (>0 means active).
+/// `calls` is the subroutine call stack of this branch.
+#[allow(clippy::too_many_arguments)]
 fn exec(
-    program: &Program,
-    chars: &[char],
+    ctx: &Ctx,
     pos: usize,
     pc: usize,
     captures: &mut [Option<usize>],
     undo_log: &mut Vec<UndoEntry>,
     depth: usize,
     ci_depth: usize,
+    calls: CallStack,
 ) -> bool {
     if depth > MAX_DEPTH {
         return false;
     }
+    let insts = ctx.insts;
+    let chars = ctx.chars;
 
     let mut pos = pos;
     let mut pc = pc;
     let mut ci_depth = ci_depth;
+    let mut calls = calls;
 
     loop {
-        if pc >= program.insts.len() {
+        if pc >= insts.len() {
             return false;
         }
-        match &program.insts[pc] {
-            Inst::Match => {
+        ctx.emit(TraceEvent::Step { pc, pos });
+        match &insts[pc] {
+            Inst::Match | Inst::MatchPattern(_) => {
+                if !ctx.allow_empty && captures[0] == Some(pos) {
+                    return false;
+                }
+                if ctx.end_at.is_some_and(|end| end != pos) {
+                    return false;
+                }
                 // Record end of full match
                 captures[1] = Some(pos);
                 return true;
             }
-            Inst::Char(expected) => {
-                if pos < chars.len() && char_eq(chars[pos], *expected, ci_depth > 0) {
-                    pos += 1;
+            Inst::Char(expected) => match next_char(ctx, pos) {
+                Some((ch, next)) if char_eq(ch, *expected, ci_depth > 0) => {
+                    pos = next;
                     pc += 1;
-                } else {
-                    return false;
                 }
-            }
-            Inst::AnyChar => {
-                if pos < chars.len() && chars[pos] != '\n' {
-                    pos += 1;
+                _ => return false,
+            },
+            Inst::Str(text) => match match_text(ctx, pos, text, ci_depth > 0) {
+                Some(next) => {
+                    pos = next;
                     pc += 1;
-                } else {
-                    return false;
                 }
-            }
-            Inst::CharClass { ranges, negated } => {
-                if pos < chars.len() && char_class_matches(chars[pos], ranges, *negated, ci_depth > 0) {
-                    pos += 1;
+                None => return false,
+            },
+            Inst::AnyChar => match next_char(ctx, pos) {
+                Some((ch, next)) if ch != '\n' => {
+                    pos = next;
                     pc += 1;
-                } else {
-                    return false;
                 }
-            }
-            Inst::ShorthandClass(kind) => {
-                if pos < chars.len() && shorthand_matches(chars[pos], *kind) {
-                    pos += 1;
+                _ => return false,
+            },
+            Inst::CharClass(set) => match next_char(ctx, pos) {
+                Some((ch, next)) if set.contains(ch) => {
+                    pos = next;
                     pc += 1;
-                } else {
-                    return false;
                 }
-            }
+                _ => return false,
+            },
+            Inst::ShorthandClass(kind) => match next_char(ctx, pos) {
+                Some((ch, next)) if shorthand_matches(ch, *kind) => {
+                    pos = next;
+                    pc += 1;
+                }
+                _ => return false,
+            },
             Inst::Jump(target) => {
                 pc = *target;
             }
             Inst::Split(first, second) => {
-                let first = *first;
-                let second = *second;
-                // Save undo log position before trying first branch
-                let undo_mark = undo_log.len();
-                let saved_ci = ci_depth;
-                if exec(program, chars, pos, first, captures, undo_log, depth + 1, ci_depth) {
+                if try_branch(ctx, pos, *first, captures, undo_log, depth, ci_depth, &calls) {
                     return true;
                 }
-                // Restore captures from undo log
-                while undo_log.len() > undo_mark {
-                    let (slot, old_val) = undo_log.pop().unwrap();
-                    captures[slot] = old_val;
-                }
-                ci_depth = saved_ci;
                 // Try second branch (tail call — continue loop)
-                pc = second;
+                pc = *second;
+            }
+            Inst::RepeatStart(slot) => {
+                let slot = *slot;
+                undo_log.push((slot, captures[slot]));
+                captures[slot] = Some(0);
+                pc += 1;
+            }
+            Inst::RepeatCheck { slot, min, max, greedy, exit } => {
+                let count = captures[*slot].unwrap_or(0);
+                if count < *min {
+                    pc += 1;
+                } else if Some(count) == *max {
+                    pc = *exit;
+                } else {
+                    let (first, second) = if *greedy { (pc + 1, *exit) } else { (*exit, pc + 1) };
+                    if try_branch(ctx, pos, first, captures, undo_log, depth, ci_depth, &calls) {
+                        return true;
+                    }
+                    pc = second;
+                }
+            }
+            Inst::RepeatIncr(slot) => {
+                let slot = *slot;
+                undo_log.push((slot, captures[slot]));
+                captures[slot] = Some(captures[slot].unwrap_or(0) + 1);
+                pc += 1;
             }
             Inst::Save(slot) => {
                 let slot = *slot;
@@ -193,12 +673,38 @@ fn exec(
             }
             Inst::AssertEnd => {
                 if pos == chars.len() {
+                    ctx.touch_end();
+                    pc += 1;
+                } else {
+                    return false;
+                }
+            }
+            Inst::AssertTextStart => {
+                if pos == 0 {
+                    pc += 1;
+                } else {
+                    return false;
+                }
+            }
+            Inst::AssertTextEnd => {
+                if pos == chars.len() {
+                    ctx.touch_end();
+                    pc += 1;
+                } else {
+                    return false;
+                }
+            }
+            Inst::AssertSearchStart => {
+                if pos == ctx.search_start {
                     pc += 1;
                 } else {
                     return false;
                 }
             }
             Inst::AssertWordBoundary => {
+                if pos == chars.len() {
+                    ctx.touch_end();
+                }
                 if is_word_boundary(chars, pos) {
                     pc += 1;
                 } else {
@@ -206,6 +712,9 @@ fn exec(
                 }
             }
             Inst::AssertNonWordBoundary => {
+                if pos == chars.len() {
+                    ctx.touch_end();
+                }
                 if !is_word_boundary(chars, pos) {
                     pc += 1;
                 } else {
@@ -217,31 +726,90 @@ fn exec(
                 let start_slot = group_idx * 2;
                 let end_slot = group_idx * 2 + 1;
                 match (captures[start_slot], captures[end_slot]) {
-                    (Some(gs), Some(ge)) => {
-                        let group_len = ge - gs;
-                        if pos + group_len <= chars.len()
-                            && if ci_depth > 0 {
-                                chars[gs..ge].iter().zip(&chars[pos..pos + group_len])
-                                    .all(|(a, b)| char_eq(*a, *b, true))
-                            } else {
-                                chars[gs..ge] == chars[pos..pos + group_len]
-                            }
-                        {
-                            pos += group_len;
+                    (Some(gs), Some(ge)) => match match_text(ctx, pos, &chars[gs..ge], ci_depth > 0) {
+                        Some(next) => {
+                            pos = next;
                             pc += 1;
-                        } else {
-                            return false;
                         }
-                    }
+                        None => return false,
+                    },
                     _ => return false,
                 }
             }
+            Inst::Call { group, target, ci_depth: callee_ci } => {
+                let call_depth = calls.as_ref().map_or(0, |f| f.depth) + 1;
+                if call_depth > MAX_CALL_DEPTH {
+                    return false;
+                }
+                calls = Some(Rc::new(CallFrame {
+                    group: *group,
+                    return_pc: pc + 1,
+                    saved_captures: captures.to_vec(),
+                    ci_depth,
+                    depth: call_depth,
+                    parent: calls,
+                }));
+                ci_depth = *callee_ci;
+                pc = *target;
+            }
+            Inst::Return(group) => {
+                match calls.take() {
+                    Some(frame) if frame.group == *group => {
+                        // Captures set inside the call revert to the caller's
+                        // values (slots 0 and 1 track the overall match).
+                        for (i, &saved) in frame.saved_captures.iter().enumerate().skip(2) {
+                            if saved != captures[i] {
+                                undo_log.push((i, captures[i]));
+                                captures[i] = saved;
+                            }
+                        }
+                        ci_depth = frame.ci_depth;
+                        pc = frame.return_pc;
+                        calls = frame.parent.clone();
+                    }
+                    // Not returning from a call to this group: fall through.
+                    other => {
+                        calls = other;
+                        pc += 1;
+                    }
+                }
+            }
+            Inst::GroupCheck(group_idx, no_target) => {
+                // A group counts as matched once both its slots are set,
+                // mirroring the participation test in Backref.
+                let group_idx = *group_idx;
+                if captures[group_idx * 2].is_some() && captures[group_idx * 2 + 1].is_some() {
+                    pc += 1;
+                } else {
+                    pc = *no_target;
+                }
+            }
+            Inst::Atomic(sub_start, sub_end) => {
+                let sub_start = *sub_start;
+                let sub_end = *sub_end;
+                // Match the group in isolation: once `exec_sub` returns, every
+                // backtrack point inside the group is gone.
+                let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
+                let mut sub_undo = Vec::new();
+                if exec_sub(ctx, pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                    for i in 2..captures.len() {
+                        if sub_captures[i] != captures[i] {
+                            undo_log.push((i, captures[i]));
+                            captures[i] = sub_captures[i];
+                        }
+                    }
+                    pos = sub_captures[1].unwrap();
+                    pc = sub_end;
+                } else {
+                    return false;
+                }
+            }
             Inst::LookaheadPositive(sub_start, sub_end) => {
                 let sub_start = *sub_start;
                 let sub_end = *sub_end;
                 let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
                 let mut sub_undo = Vec::new();
-                if exec_sub(program, chars, pos, sub_start, sub_end, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                if exec_sub(&ctx.lookahead(), pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
                     // Propagate capture groups (skip slots 0,1 which are full match bounds)
                     for i in 2..captures.len() {
                         if sub_captures[i] != captures[i] {
@@ -259,7 +827,7 @@ fn exec(
                 let sub_end = *sub_end;
                 let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
                 let mut sub_undo = Vec::new();
-                if !exec_sub(program, chars, pos, sub_start, sub_end, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                if !exec_sub(&ctx.lookahead(), pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
                     pc = sub_end;
                 } else {
                     return false;
@@ -268,13 +836,43 @@ fn exec(
             Inst::LookbehindPositive(sub_start, sub_end) => {
                 let sub_start = *sub_start;
                 let sub_end = *sub_end;
+                // The body is compiled in reverse: run it backward from here.
+                let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
+                let mut sub_undo = Vec::new();
+                if exec_sub(&ctx.direction(true), pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                    for i in 2..captures.len() {
+                        if sub_captures[i] != captures[i] {
+                            undo_log.push((i, captures[i]));
+                            captures[i] = sub_captures[i];
+                        }
+                    }
+                    pc = sub_end;
+                } else {
+                    return false;
+                }
+            }
+            Inst::LookbehindNegative(sub_start, sub_end) => {
+                let sub_start = *sub_start;
+                let sub_end = *sub_end;
+                let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
+                let mut sub_undo = Vec::new();
+                if !exec_sub(&ctx.direction(true), pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                    pc = sub_end;
+                } else {
+                    return false;
+                }
+            }
+            Inst::LookbehindScanPositive(sub_start, sub_end) => {
+                let sub_start = *sub_start;
+                let sub_end = *sub_end;
+                let fwd = ctx.direction(false);
                 // Try all possible lengths behind the current position
                 let mut found = false;
                 for lookback in 0..=pos {
                     let try_pos = pos - lookback;
                     let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
                     let mut sub_undo = Vec::new();
-                    if exec_sub(program, chars, try_pos, sub_start, sub_end, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
+                    if exec_sub(&fwd, try_pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
                         // The sub-match must end exactly at `pos`
                         if sub_captures[1] == Some(pos) {
                             // Propagate capture groups back (skip slots 0,1)
@@ -295,19 +893,20 @@ fn exec(
                     return false;
                 }
             }
-            Inst::LookbehindNegative(sub_start, sub_end) => {
+            Inst::LookbehindScanNegative(sub_start, sub_end) => {
                 let sub_start = *sub_start;
                 let sub_end = *sub_end;
+                let fwd = ctx.direction(false);
                 let mut found = false;
                 for lookback in 0..=pos {
                     let try_pos = pos - lookback;
                     let mut sub_captures: Vec<Option<usize>> = captures.to_vec();
                     let mut sub_undo = Vec::new();
-                    if exec_sub(program, chars, try_pos, sub_start, sub_end, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth) {
-                        if sub_captures[1] == Some(pos) {
-                            found = true;
-                            break;
-                        }
+                    if exec_sub(&fwd, try_pos, sub_start, &mut sub_captures, &mut sub_undo, depth + 1, ci_depth)
+                        && sub_captures[1] == Some(pos)
+                    {
+                        found = true;
+                        break;
                     }
                 }
                 if !found {
@@ -321,9 +920,7 @@ fn exec(
                 pc += 1;
             }
             Inst::CaseInsensitiveOff => {
-                if ci_depth > 0 {
-                    ci_depth -= 1;
-                }
+                ci_depth = ci_depth.saturating_sub(1);
                 pc += 1;
             }
             Inst::Nop => {
@@ -333,14 +930,39 @@ fn exec(
     }
 }
 
+/// Try the preferred branch of a split at `pc`. On failure the capture slots
+/// are rolled back to their state before the attempt, ready for the other branch.
+#[allow(clippy::too_many_arguments)]
+fn try_branch(
+    ctx: &Ctx,
+    pos: usize,
+    pc: usize,
+    captures: &mut [Option<usize>],
+    undo_log: &mut Vec<UndoEntry>,
+    depth: usize,
+    ci_depth: usize,
+    calls: &CallStack,
+) -> bool {
+    // Save undo log position before trying the branch
+    let undo_mark = undo_log.len();
+    if exec(ctx, pos, pc, captures, undo_log, depth + 1, ci_depth, calls.clone()) {
+        return true;
+    }
+    ctx.emit(TraceEvent::Backtrack { pc, pos });
+    // Restore captures from undo log
+    while undo_log.len() > undo_mark {
+        let (slot, old_val) = undo_log.pop().unwrap();
+        captures[slot] = old_val;
+    }
+    false
+}
+
 /// Execute a sub-program (used for lookaround).
 /// The sub-program runs from `sub_start` up to (but not including) the Match at sub_end-1.
 fn exec_sub(
-    program: &Program,
-    chars: &[char],
+    ctx: &Ctx,
     pos: usize,
     sub_start: usize,
-    _sub_end: usize,
     captures: &mut [Option<usize>],
     undo_log: &mut Vec<UndoEntry>,
     depth: usize,
@@ -349,53 +971,20 @@ fn exec_sub(
     // We run the sub-program starting at sub_start.
     // The sub-program ends with a Match instruction.
     // We save capture[1] to track where the sub-match ends.
+    // The empty-match and end restrictions only apply to the top-level match.
+    let sub_ctx = Ctx { allow_empty: true, end_at: None, ..*ctx };
     let old_cap1 = captures[1];
     captures[1] = None;
-    let result = exec(program, chars, pos, sub_start, captures, undo_log, depth, ci_depth);
+    // Sub-programs are self-contained, so they start with an empty call stack.
+    let result = exec(&sub_ctx, pos, sub_start, captures, undo_log, depth, ci_depth, None);
     if !result {
         captures[1] = old_cap1;
     }
     result
 }
 
-/// Check if a character matches a character class.
-fn char_class_matches(ch: char, items: &[ClassItem], negated: bool, case_insensitive: bool) -> bool {
-    let mut matched = false;
-    for item in items {
-        match item {
-            ClassItem::Literal(c) => {
-                if char_eq(ch, *c, case_insensitive) {
-                    matched = true;
-                    break;
-                }
-            }
-            ClassItem::Range(lo, hi) => {
-                if case_insensitive {
-                    let ch_lower = ch.to_ascii_lowercase();
-                    let lo_lower = lo.to_ascii_lowercase();
-                    let hi_lower = hi.to_ascii_lowercase();
-                    if ch_lower >= lo_lower && ch_lower <= hi_lower {
-                        matched = true;
-                        break;
-                    }
-                } else if ch >= *lo && ch <= *hi {
-                    matched = true;
-                    break;
-                }
-            }
-            ClassItem::Shorthand(kind) => {
-                if shorthand_matches(ch, *kind) {
-                    matched = true;
-                    break;
-                }
-            }
-        }
-    }
-    if negated { !matched } else { matched }
-}
-
 /// Check if a character matches a shorthand class.
-fn shorthand_matches(ch: char, kind: ShorthandKind) -> bool {
+pub(crate) fn shorthand_matches(ch: char, kind: ShorthandKind) -> bool {
     match kind {
         ShorthandKind::Digit => ch.is_ascii_digit(),
         ShorthandKind::NonDigit => !ch.is_ascii_digit(),
@@ -407,7 +996,7 @@ fn shorthand_matches(ch: char, kind: ShorthandKind) -> bool {
 }
 
 /// Check if `pos` is at a word boundary.
-fn is_word_boundary(chars: &[char], pos: usize) -> bool {
+pub(crate) fn is_word_boundary(chars: &[char], pos: usize) -> bool {
     let before = if pos > 0 {
         is_word_char(chars[pos - 1])
     } else {
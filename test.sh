@@ -32,49 +32,150 @@ SKIP=0
 TOTAL=0
 ERRORS=""
 
+# In fast mode, only run ~10% of tests, deterministic per seed
+sampled_out() {
+    if [ "$FAST" = true ]; then
+        HASH=$(echo "${SEED}:${TOTAL}" | md5sum | head -c 4)
+        HASH_DEC=$((16#$HASH))
+        if [ $((HASH_DEC % 100)) -ge $SAMPLE_PERCENT ]; then
+            SKIP=$((SKIP + 1))
+            return 0
+        fi
+    fi
+    return 1
+}
+
+# Runs `regex-engine ARGS... PATTERN INPUT` and compares its output, followed
+# by `[exit N]`, against Python's `re`. ORACLE is a Python expression over
+# `pattern`, `text` and `args` whose value is the expected output; it defaults
+# to `show(re.search(pattern, text))`, the MATCH/GROUP/NO_MATCH lines the
+# search prints. If it raises, the search is expected to print
+# `ERROR:<message>` and a subcommand to exit with status 2. An ARGS element
+# `{pattern}` puts PATTERN there instead of after ARGS (for `--replace`).
+# EXPECTED only documents the result.
 run_test() {
     local pattern="$1"
     local input="$2"
     local expected="$3"
     local description="$4"
+    local oracle="${5:-show(re.search(pattern, text))}"
+    local args=("${@:6}")
     TOTAL=$((TOTAL + 1))
+    if sampled_out; then
+        return
+    fi
 
-    # In fast mode, only run ~10% of tests, deterministic per seed
-    if [ "$FAST" = true ]; then
-        HASH=$(echo "${SEED}:${TOTAL}" | md5sum | head -c 4)
-        HASH_DEC=$((16#$HASH))
-        if [ $((HASH_DEC % 100)) -ge $SAMPLE_PERCENT ]; then
-            SKIP=$((SKIP + 1))
-            return
+    local cmd=()
+    local placed=false
+    for arg in "${args[@]}"; do
+        if [ "$arg" = "{pattern}" ]; then
+            cmd+=("$pattern")
+            placed=true
+        else
+            cmd+=("$arg")
         fi
+    done
+    if [ "$placed" = false ]; then
+        cmd+=("$pattern")
     fi
+    cmd+=("$input")
 
     # Compare against Python's re module as oracle
     # Pass pattern/input via argv to avoid Python string escape interpretation
-    EXPECTED_OUTPUT=$(python3 - "$pattern" "$input" <<'PYEOF'
-import re, sys
-pattern = sys.argv[1]
-text = sys.argv[2]
+    EXPECTED_OUTPUT=$(python3 - "$pattern" "$input" "$oracle" "${args[@]}" <<'PYEOF'
+import json, re, sys
+pattern, text, oracle = sys.argv[1:4]
+args = sys.argv[4:]
+
+def show(m, unset=''):
+    if not m:
+        return 'NO_MATCH'
+    groups = [f'GROUP {i}:{unset if g is None else g}' for i, g in enumerate(m.groups(), 1)]
+    return '\n'.join(['MATCH:' + m.group(0)] + groups)
+
+def finditer_json():
+    r = re.compile(pattern)
+    names = {i: n for n, i in r.groupindex.items()}
+    def span(m, i):
+        s, e = m.span(i)
+        if s < 0:
+            return {'start': None, 'end': None, 'text': None}
+        return {'start': s, 'end': e, 'text': m.group(i)}
+    matches = []
+    for m in r.finditer(text):
+        groups = []
+        for i in range(1, r.groups + 1):
+            g = {'group': i}
+            if i in names:
+                g['name'] = names[i]
+            g.update(span(m, i))
+            groups.append(g)
+        matches.append({**span(m, 0), 'groups': groups})
+    return json.dumps({'matches': matches}, separators=(',', ':'), ensure_ascii=False)
+
 try:
-    m = re.search(pattern, text)
-    if m:
-        print('MATCH:' + m.group(0))
-        for i, g in enumerate(m.groups(), 1):
-            print(f'GROUP {i}:{g if g is not None else ""}')
-    else:
-        print('NO_MATCH')
+    print(eval(oracle))
+    print('[exit 0]')
 except Exception as e:
-    print('ERROR:' + str(e))
+    if args and args[0] in ('--grep', '--replace', '--split'):
+        print('[exit 2]')
+    else:
+        print('ERROR:' + str(e))
+        print('[exit 0]')
 PYEOF
 )
 
-    ACTUAL_OUTPUT=$($BINARY "$pattern" "$input" 2>&1) || true
+    ACTUAL_OUTPUT=$($BINARY "${cmd[@]}" 2>/dev/null; echo "[exit $?]")
 
     if [ "$EXPECTED_OUTPUT" = "$ACTUAL_OUTPUT" ]; then
         PASS=$((PASS + 1))
     else
         FAIL=$((FAIL + 1))
-        ERRORS="${ERRORS}\nFAIL test ${TOTAL}: pattern='${pattern}' input='${input}' desc='${description}'\n  expected: ${EXPECTED_OUTPUT}\n  actual:   ${ACTUAL_OUTPUT}"
+        ERRORS="${ERRORS}\nFAIL test ${TOTAL}: args='${args[*]}' pattern='${pattern}' input='${input}' desc='${description}'\n  expected: ${EXPECTED_OUTPUT}\n  actual:   ${ACTUAL_OUTPUT}"
+    fi
+}
+
+# Like run_test, but compares against the literal expected output instead of
+# the Python oracle. Use for syntax that Python's `re` does not support.
+run_expect() {
+    local pattern="$1"
+    local input="$2"
+    local expected="$3"
+    local description="$4"
+    TOTAL=$((TOTAL + 1))
+    if sampled_out; then
+        return
+    fi
+
+    ACTUAL_OUTPUT=$($BINARY "$pattern" "$input" 2>&1) || true
+
+    if [ "$expected" = "$ACTUAL_OUTPUT" ]; then
+        PASS=$((PASS + 1))
+    else
+        FAIL=$((FAIL + 1))
+        ERRORS="${ERRORS}\nFAIL test ${TOTAL}: pattern='${pattern}' input='${input}' desc='${description}'\n  expected: ${expected}\n  actual:   ${ACTUAL_OUTPUT}"
+    fi
+}
+
+# Runs `regex-engine ARGS...` with STDIN on standard input and compares its
+# output, followed by `[exit N]`, against EXPECTED.
+run_cmd_expect() {
+    local stdin="$1"
+    local expected="$2"
+    local description="$3"
+    shift 3
+    TOTAL=$((TOTAL + 1))
+    if sampled_out; then
+        return
+    fi
+
+    ACTUAL_OUTPUT=$(printf '%s' "$stdin" | $BINARY "$@" 2>&1; echo "[exit $?]")
+
+    if [ "$expected" = "$ACTUAL_OUTPUT" ]; then
+        PASS=$((PASS + 1))
+    else
+        FAIL=$((FAIL + 1))
+        ERRORS="${ERRORS}\nFAIL test ${TOTAL}: args='$*' desc='${description}'\n  expected: ${expected}\n  actual:   ${ACTUAL_OUTPUT}"
     fi
 }
 
@@ -327,6 +428,348 @@ run_test "()*" "" "MATCH:" "empty group star on empty"
 # === LAZY THEN GREEDY INTERACTION ===
 run_test "(a+?)(a+)" "aaaa" "MATCH:aaaa" "lazy then greedy captures all"
 
+# === CANONICALIZED CHARACTER CLASSES ===
+run_test "[A-Za-z0-9._%+-]+" "mail: john.doe+x@ex.com" "MATCH:john.doe+x" "email local-part class"
+run_test "[a-cb-ex-z]+" "abcdexyzf" "MATCH:abcdexyz" "overlapping ranges merge"
+run_test "[^\\w\\s]+" "ab !?# cd" "MATCH:!?#" "negated class of shorthands"
+run_test "(?i:[^a])" "Ab" "MATCH:b" "case-insensitive negated class"
+run_test "(?i:[b-y]+)" "AbCYz" "MATCH:bCY" "case-insensitive range folding"
+run_test "[Z-a]+" "YZ[_\`ab" "MATCH:Z[_\`a" "range spanning punctuation"
+run_test "[é-ü]+" "caféü" "MATCH:éü" "non-ASCII range"
+run_test "[^a-z]" "abcé" "MATCH:é" "negated class matches non-ASCII"
+run_test "[\\D]+" "12ab3" "MATCH:ab" "negated shorthand inside class"
+
+# === HEX, UNICODE AND CONTROL ESCAPES ===
+run_test "\\x41\\x42" "xxABxx" "MATCH:AB" "hex escapes"
+run_test "[\\x41-\\x43]+" "ABCD" "MATCH:ABC" "hex escape range in class"
+run_test "\\u00e9" "café" "MATCH:é" "4-digit unicode escape"
+run_test "\\U0001F600" "hi 😀" "MATCH:😀" "8-digit unicode escape"
+run_test "\\101\\102" "AB" "MATCH:AB" "octal escapes"
+run_test "a\\0" "a" "NO_MATCH" "null escape"
+run_test "[\\1]" "a" "NO_MATCH" "octal digit escape in class"
+run_test "\\N{LATIN SMALL LETTER A}+" "baaa" "MATCH:aaa" "named character escape"
+run_test "\\N{EM DASH}" "a—b" "MATCH:—" "named typographic character"
+run_expect "\\x4" "x4" "ERROR:incomplete escape \\x4" "incomplete hex escape"
+run_expect "\\u12" "u12" "ERROR:incomplete escape \\u12" "incomplete unicode escape"
+run_expect "\\N{NOT A REAL NAME}" "x" "ERROR:undefined character name 'NOT A REAL NAME' (only ASCII and common typographic names are known)" "unknown character name"
+run_expect "\\N{GREEK SMALL LETTER ALPHA}" "α" "ERROR:undefined character name 'GREEK SMALL LETTER ALPHA' (only ASCII and common typographic names are known)" "names outside the built-in table are errors"
+run_expect "a\\q" "aq" "ERROR:bad escape \\q" "unknown letter escape"
+run_expect "[\\q]" "q" "ERROR:bad escape \\q" "unknown letter escape in a class"
+run_expect "\\E" "E" "ERROR:bad escape \\E" "unknown uppercase letter escape"
+run_test "a\\-\\#\\ " "a-# " "MATCH:a-# " "escaped punctuation is literal"
+run_test "(a)\\1\\111" "aaI" "MATCH:aaI" "octal escape after backreference"
+run_expect "\\x{41}\\x{1F600}" "A😀" "MATCH:A😀" "braced hex escapes"
+run_expect "\\x{110000}" "x" "ERROR:bad escape \\x{110000}" "braced hex escape out of range"
+run_expect "[\\x{30}-\\x{39}]+" "ab123" "MATCH:123" "braced hex range in class"
+
+# === STRING ANCHORS ===
+run_test "\\Aabc" "abcabc" "MATCH:abc" "absolute start anchor"
+run_test "\\Abc" "abc" "NO_MATCH" "absolute start anchor no match"
+run_test "abc\\Z" "abcabc" "MATCH:abc" "absolute end anchor"
+run_test "abc\\Z" "abcd" "NO_MATCH" "absolute end anchor no match"
+run_test "a|\\Ab" "bab" "MATCH:b" "absolute start in alternation"
+run_expect "\\w+\\z" "one two" "MATCH:two" "lowercase end anchor"
+run_expect "\\Gab" "abab" "MATCH:ab" "search-start anchor at start"
+run_expect "\\Gb" "ab" "NO_MATCH" "search-start anchor does not float"
+
+# === ATOMIC GROUPS AND POSSESSIVE QUANTIFIERS ===
+run_test "(?>a+)b" "aaab" "MATCH:aaab" "atomic group then literal"
+run_test "(?>a+)a" "aaaa" "NO_MATCH" "atomic group gives nothing back"
+run_test "(?>ab|a)b" "ab" "NO_MATCH" "atomic alternation commits to first branch"
+run_test "(?>ab|a)b" "abb" "MATCH:abb" "atomic alternation first branch succeeds"
+run_test "((?>a*))b" "aab" "MATCH:aab" "capture inside atomic group"
+run_test "a*+a" "aaa" "NO_MATCH" "possessive star"
+run_test "a++b" "aaab" "MATCH:aaab" "possessive plus"
+run_test "a?+a" "a" "NO_MATCH" "possessive question"
+run_test "a{1,3}+a" "aaa" "NO_MATCH" "possessive range"
+run_test "a{1,3}+a" "aaaa" "MATCH:aaaa" "possessive range leaves remainder"
+run_test "\"[^\"]*+\"" "say \"hi\" now" "MATCH:\"hi\"" "possessive quoted string"
+run_test "(\\w)++\\1" "abcc" "NO_MATCH" "possessive group repeat"
+run_test "(?:a|b)*+c" "ababc" "MATCH:ababc" "possessive non-capturing group"
+
+# === NAMED GROUPS ===
+run_test "(?P<year>\\d{4})-(?P<mon>\\d\\d)" "on 2024-05" "MATCH:2024-05" "named groups"
+run_test "(?P<ch>\\w)(?P=ch)" "abccd" "MATCH:cc" "named backreference"
+run_expect "(?P<a>x)(?P<a>y)" "xy" "ERROR:redefinition of group name 'a'" "duplicate group name"
+run_expect "(?<word>\\w+) (?P=word)" "say hi hi" $'MATCH:hi hi\nGROUP 1:hi' "angle-bracket named group"
+run_expect "(a)\\2" "aa" "ERROR:invalid group reference 2" "backreference to missing group"
+
+# === CONDITIONAL PATTERNS ===
+run_test "(\")?\\w+(?(1)\")" "\"quoted\"" "MATCH:\"quoted\"" "conditional balanced quote"
+run_test "(\")?\\w+(?(1)\")" "bare" "MATCH:bare" "conditional quote absent"
+run_test "^(\")?\\w+(?(1)\")$" "\"open" "NO_MATCH" "conditional unbalanced quote"
+run_test "(a)?(?(1)b|c)" "ab" "MATCH:ab" "conditional yes branch"
+run_test "(a)?(?(1)b|c)" "c" "MATCH:c" "conditional no branch"
+run_test "(a)?(?(1)b|c)" "ac" "MATCH:c" "conditional forces retry"
+run_test "(?P<q><)?\\w+(?(q)>)" "<tag>" "MATCH:<tag>" "named conditional"
+run_test "(?:(a)|b)(?(1)x|y)" "by" "MATCH:by" "conditional after alternation"
+run_expect "(a)(?(1)b|c|d)" "ab" "ERROR:conditional backref with more than two branches" "conditional too many branches"
+run_expect "(?(2)a|b)" "b" "ERROR:invalid group reference 2" "conditional on missing group"
+
+# === RECURSION AND SUBROUTINE CALLS ===
+run_expect "\\((?:[^()]|(?R))*\\)" "x(a(b)c(d(e)))y" "MATCH:(a(b)c(d(e)))" "whole-pattern recursion balanced parens"
+run_expect "^(\\((?:[^()]|(?1))*\\))\$" "(()(()))" $'MATCH:(()(()))\nGROUP 1:(()(()))' "group recursion balanced"
+run_expect "^(\\((?:[^()]|(?1))*\\))\$" "(()(())" "NO_MATCH" "group recursion unbalanced"
+run_expect "(?<p>\\[(?:[^\\[\\]]|(?&p))*\\])" "a[1,[2,[3]],4]" $'MATCH:[1,[2,[3]],4]\nGROUP 1:[1,[2,[3]],4]' "named recursion nested brackets"
+run_expect "^((.)(?:(?1)|.?)\\2)\$" "racecar" $'MATCH:racecar\nGROUP 1:racecar\nGROUP 2:r' "palindrome via per-call captures"
+run_expect "^((.)(?:(?1)|.?)\\2)\$" "racecbr" "NO_MATCH" "palindrome mismatch"
+run_expect "(a|b)(?1)" "xba" $'MATCH:ba\nGROUP 1:b' "subroutine does not overwrite caller group"
+run_expect "(?1)(a|b)" "ba" $'MATCH:ba\nGROUP 1:a' "forward subroutine call"
+run_expect "(a)(?-1)(?+1)(b)" "aab" "NO_MATCH" "relative subroutine calls need both"
+run_expect "(a)(?-1)(?+1)(b)" "aabb" $'MATCH:aabb\nGROUP 1:a\nGROUP 2:b' "relative subroutine calls match"
+run_expect "((?R)|x)" "x" $'MATCH:x\nGROUP 1:x' "left recursion hits call limit then backtracks"
+run_expect "(?2)(a)" "a" "ERROR:invalid group reference 2" "call to missing group"
+run_expect "(?&x)(?<x>a)" "aa" $'MATCH:aa\nGROUP 1:a' "forward named subroutine call"
+run_expect "(?P>x)(?P<x>a)" "aa" $'MATCH:aa\nGROUP 1:a' "forward (?P>name) call"
+run_expect "^(?<a>x(?&b)?)(?<b>y(?&a)?)\$" "xyxy" $'MATCH:xyxy\nGROUP 1:xyx\nGROUP 2:y' "mutually recursive named groups"
+run_expect "(?&y)(?<x>a)" "a" "ERROR:unknown group name 'y'" "call to undefined name"
+
+# === BRANCH RESET GROUPS ===
+run_expect "(?|(\\d+)-(\\w)|(\\w)=(\\d+))" "x=42" $'MATCH:x=42\nGROUP 1:x\nGROUP 2:42' "branch reset second alternative"
+run_expect "(?|(\\d+)-(\\w)|(\\w)=(\\d+))" "7-q" $'MATCH:7-q\nGROUP 1:7\nGROUP 2:q' "branch reset first alternative"
+run_expect "(?|a(x)|b(y)(z))(w)" "byzw" $'MATCH:byzw\nGROUP 1:y\nGROUP 2:z\nGROUP 3:w' "group after branch reset uses max count"
+run_expect "(?|a(x)|b(y)(z))(w)" "axw" $'MATCH:axw\nGROUP 1:x\nGROUP 2:\nGROUP 3:w' "shorter alternative leaves group unset"
+run_expect "(?|(\\d{4})-(\\d\\d)|(\\d\\d)/(\\d{4}))" "on 05/2024" $'MATCH:05/2024\nGROUP 1:05\nGROUP 2:2024' "branch reset date formats"
+run_expect "(?|(?<d>\\d+)/|(?<d>\\w+):)(?P=d)" "ab:ab" $'MATCH:ab:ab\nGROUP 1:ab' "duplicate name across branch reset alternatives"
+run_expect "(?|(?<a>x)|(y)(?<a>z))" "x" "ERROR:redefinition of group name 'a'" "duplicate name with different numbers"
+
+# === COUNTED REPETITION ===
+run_test "a{10}" "aaaaaaaaaaaa" "MATCH:aaaaaaaaaa" "counted exact repetition"
+run_test "^a{10}\$" "aaaaaaaaa" "NO_MATCH" "counted exact repetition too short"
+run_test "\\w{2,12}?x" "abcdefghijx" "MATCH:abcdefghijx" "lazy counted range"
+run_test "(a{2,12}?)(a*)" "aaaaaaaaaaaaaaa" "MATCH:aaaaaaaaaaaaaaa" "lazy counted range captures"
+run_test "(?:(\\d)x){9,}" "1x2x3x4x5x6x7x8x9x0xy" "MATCH:1x2x3x4x5x6x7x8x9x0x" "counted at-least with capture"
+run_test "(a|ab){9}c" "aabababababababac" "MATCH:aabababababababac" "counted loop backtracks into body"
+run_test "(?:a{3}){12}" "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" "MATCH:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" "nested counted loops"
+run_test "^.{1,4096}\$" "some line of text" "MATCH:some line of text" "length-validating dot range"
+run_test "(a{1000}){1000}" "aaa" "NO_MATCH" "huge nested repetition compiles small"
+run_test "(\\w)\\1{9}" "xaaaaaaaaaa" "MATCH:aaaaaaaaaa" "counted backreference"
+run_test "(((((((a{8}){8}){8}){8}){8}){8}){8})" "aaaa" "NO_MATCH" "nested small repetitions compile small"
+run_test "(?:(?:(?:(?:(?:(?:(?:\\w{1,8}){1,8}){1,8}){1,8}){1,8}){1,8}){1,8})" "hello world" "MATCH:hello" "nested small ranges compile small"
+run_test "((a{8}){8}){8}" "$(printf 'a%.0s' {1..600})" "MATCH:$(printf 'a%.0s' {1..512})" "nested small repetitions match"
+
+# === LITERAL PREFILTERS ===
+echo "=== Literal Prefilters ==="
+run_test "hello" "say hello world" "MATCH:hello" "single literal prefix"
+run_test "foo|bar|baz" "xx baz bar" "MATCH:baz" "alternation prefix set"
+run_test "(foo|foobar)x" "foobarx" "MATCH:foobarx" "overlapping prefix alternatives"
+run_test "ab?c" "xxacxabc" "MATCH:ac" "optional char in prefix"
+run_test "[xy]z" "aazyzxz" "MATCH:yz" "small class expands into prefix set"
+run_test "(?i:hello)" "say HeLLo" "MATCH:HeLLo" "case-insensitive prefix"
+run_test "\\w+@example\\.com" "mail bob@example.com now" "MATCH:bob@example.com" "required inner literal"
+run_test "\\w+@example\\.com" "mail bob@example.org now" "NO_MATCH" "missing required literal"
+run_test "\\d+px" "width 12pt 40px" "MATCH:40px" "required suffix"
+run_test "(?<=a)bc" "bcabc" "MATCH:bc" "prefix after lookbehind"
+run_test "ab(?=cd)" "abxabcd" "MATCH:ab" "prefix before lookahead"
+run_test "(a)(?(1)bc|de)" "xabc" "MATCH:abc" "conditional prefix union"
+run_test "foo(?:bar)*" "xfoobarbar" "MATCH:foobarbar" "prefix before star"
+run_test "a{3}b" "aabaaab" "MATCH:aaab" "repeated literal prefix"
+
+# === AHO-CORASICK LITERAL SETS ===
+echo "=== Aho-Corasick Literal Sets ==="
+run_test "error|fatal|panic|abort|crash|failure|denied|refused|timeout|overflow|corrupt|segfault|killed|oom|deadlock|exception" "all good, then a timeout and a crash" "MATCH:timeout" "literal alternation matcher"
+run_test "error|fatal|panic|abort|crash|failure|denied|refused|timeout|overflow|corrupt|segfault|killed|oom|deadlock|exception" "all good here" "NO_MATCH" "literal alternation no match"
+run_test "\\b(?:error|fatal|panic|abort|crash|failure|denied|refused|timeout|overflow|corrupt|segfault|killed|oom|deadlock|exception)\\b" "errors then fatal error" "MATCH:fatal" "keyword list prefilter with word boundaries"
+run_test "(?:error|fatal|panic|abort|crash|failure|denied|refused|timeout|overflow|corrupt|segfault|killed|oom|deadlock|exception): (\\d+)" "panic at 3, panic: 42" "MATCH:panic: 42"$'\n'"GROUP 1:42" "keyword list prefilter with capture"
+run_test "ab|abcd|b|a|c|d|e|f|g|h" "xabcd" "MATCH:ab" "leftmost-first among overlapping literals"
+run_test "abcd|ab|b|a|c|d|e|f|g|h" "xabcd" "MATCH:abcd" "first-listed literal wins"
+run_test "she|he|hers|his" "ahishers" "MATCH:his" "classic Aho-Corasick set"
+run_test "bc|abcde|x|y|z|w|v|u|t" "qabcde" "MATCH:abcde" "earlier start found after a shorter match"
+
+# === FIRST-CHARACTER SETS ===
+echo "=== First-Character Sets ==="
+run_test "[A-Z]\\w+" "hello World" "MATCH:World" "class first set"
+run_test "a*b" "xxxaab" "MATCH:aab" "optional prefix first set"
+run_test "a*b" "xxxb" "MATCH:b" "first set includes char after star"
+run_test "(?:x|y)?\\d" "ab y7" "MATCH:y7" "optional alternation first set"
+run_test "(?i:k)ey" "the Key" "MATCH:Key" "case-insensitive char in first set"
+run_test "(?>a|b)c" "xxbc" "MATCH:bc" "first set through atomic group"
+run_test "(?>a?)b" "xxb" "MATCH:b" "nullable atomic group continues"
+run_test "(?=\\w)\\d" "ab 5" "MATCH:5" "first set steps over lookahead"
+run_test "(a)?(?(1)b|c)" "xxc" "MATCH:c" "first set through conditional"
+run_test "(?:a?){10}b" "xxb" "MATCH:b" "nullable counted loop body"
+run_test "x*" "abc" "MATCH:" "nullable pattern has no first set"
+run_test "(\\w)\\1" "ab cc" "MATCH:cc"$'\n'"GROUP 1:c" "backreference after class"
+run_test ".b" $'a\nb ab' "MATCH:ab" "dot first set excludes newline"
+
+# === END ANCHORS AND SUFFIXES ===
+echo "=== End Anchors and Suffixes ==="
+run_test "\\.(tar\\.gz|zip)\$" "backup.tar.gz" "MATCH:.tar.gz"$'\n'"GROUP 1:tar.gz" "extension suffix set"
+run_test "\\.(tar\\.gz|zip)\$" "backup.tar.gz.bak" "NO_MATCH" "missing suffix rejected"
+run_test "\\.(tar\\.gz|zip)\$" "a.zip.zip" "MATCH:.zip"$'\n'"GROUP 1:zip" "bounded length starts near the end"
+run_test "\\d{2,4}\$" "12345678" "MATCH:5678" "bounded class run at end"
+run_test "\\d+\$" "12345678" "MATCH:12345678" "unbounded run at end"
+run_test "(?:ab|c)\\Z" "xxabc" "MATCH:c" "end-of-text anchor"
+run_test "a\$|b\$" "ba" "MATCH:a" "every alternative anchored"
+run_test "a\$|b" "ba" "MATCH:b" "one alternative unanchored"
+run_test "(?<=x)y\$" "xy" "MATCH:y" "lookbehind before end anchor"
+run_test "y(?=\$)" "ay" "MATCH:y" "lookahead end is not an end anchor"
+
+# === REVERSED LOOKBEHIND ===
+echo "=== Reversed Lookbehind ==="
+run_test "(?<=ab)c" "abc" "MATCH:c" "lookbehind runs backward"
+run_test "(?<=(a)(b))c" "xabc" "MATCH:c"$'\n'"GROUP 1:a"$'\n'"GROUP 2:b" "captures inside reversed lookbehind"
+run_test "(?<![ab]{2})c" "abc bc" "MATCH:c" "negative lookbehind over a class run"
+run_test "(?<=(?<=a)b)c" "bbc abc" "MATCH:c" "nested lookbehind"
+run_test "(?<=a(?=b))b" "ab" "MATCH:b" "lookahead inside lookbehind reads forward"
+run_test "(?<=\\bfoo)bar" "xfoobar foobar" "MATCH:bar" "word boundary inside lookbehind"
+run_test "(?<=(?i:AB))c" "xabc" "MATCH:c" "case-insensitive lookbehind"
+run_test "(a)(?<=\\1)b" "ab" "MATCH:ab"$'\n'"GROUP 1:a" "backreference lookbehind falls back to scanning"
+run_test "(?<=(?>ab|cd))e" "xcde" "MATCH:e" "atomic lookbehind falls back to scanning"
+run_test "\\w+\\d\$" "abc def1" "MATCH:def1" "unbounded end-anchored pattern"
+run_test "\\w+\\d\$" "abc def1 x" "NO_MATCH" "reverse check rejects end-anchored pattern"
+run_test "(?:a|b)*c\$" "ababababx" "NO_MATCH" "reverse check with repetition"
+run_test "(a)(?=\\1)\\w+\$" "aab" "MATCH:aab"$'\n'"GROUP 1:a" "reverse check skips lookahead reading a group"
+run_test "(a)(?<=\\1)\\w+\$" "aab" "MATCH:aab"$'\n'"GROUP 1:a" "reverse check skips lookbehind reading a group"
+run_test "(a)(?!\\1)\\w+\$" "aab" "MATCH:ab"$'\n'"GROUP 1:a" "reverse check skips negative lookahead reading a group"
+run_test "(?P<x>a)(?=(?(x)a|b))\\w+\$" "aab" "MATCH:aab"$'\n'"GROUP 1:a" "reverse check skips conditional in lookahead"
+
+# === AST SIMPLIFICATION AND PEEPHOLE ===
+echo "=== AST Simplification and Peephole ==="
+run_test "abc|abd" "xxabd" "MATCH:abd" "common prefix factored"
+run_test "ab|abc" "abc" "MATCH:ab" "factoring keeps alternative order"
+run_test "abc|ab" "abc" "MATCH:abc" "factoring keeps longer-first order"
+run_test "ab|b|abc" "abc" "MATCH:ab" "only adjacent alternatives factored"
+run_test "(ab)c|(ab)d" "abd" "MATCH:abd"$'\n'"GROUP 1:"$'\n'"GROUP 2:ab" "groups block factoring"
+run_test "a|b|c" "xxc" "MATCH:c" "single-char alternation as class"
+run_test "(?i:a|b)" "xB" "MATCH:B" "case-insensitive single-char alternation"
+run_test "(?:(?:a)(?:b))(?:c)" "abc" "MATCH:abc" "nested non-capturing concats flattened"
+run_test "(?:a|(?:b|c))d" "cd" "MATCH:cd" "nested alternations flattened"
+run_test "(?i:1)2" "12" "MATCH:12" "case-insensitive region without letters dropped"
+run_test "(?i:)a" "a" "MATCH:a" "empty case-insensitive region"
+run_test "x(?:|y)z" "xz xyz" "MATCH:xz" "empty alternative"
+run_test "(?:foo|foobar|fob)+x" "foobarfobx" "MATCH:foobarfobx" "factored alternation under quantifier"
+
+# === STRING INSTRUCTION ===
+echo "=== String Instruction ==="
+run_test "hello world" "say hello world" "MATCH:hello world" "literal run as one string"
+run_test "hello world" "say hello worl" "NO_MATCH" "string cut short at end of input"
+run_test "(?i:hello) world" "HeLLo world" "MATCH:HeLLo world" "case-insensitive string"
+run_test "(?i:hello) world" "HeLLo WORLD" "NO_MATCH" "string after case-insensitive region is exact"
+run_test "ab(cd)ef" "xabcdef" "MATCH:abcdef"$'\n'"GROUP 1:cd" "strings around a group"
+run_test "(?:abc)+" "abcabcab" "MATCH:abcabc" "repeated string"
+run_test "(?<=abc)d" "abcd" "MATCH:d" "string in reversed lookbehind"
+run_test "(?<!abc)d" "abcd xbcd" "MATCH:d" "string in negative lookbehind"
+run_test "(a)bc\\1" "abca" "MATCH:abca"$'\n'"GROUP 1:a" "string before backreference"
+
+echo "=== Anchored Matching ==="
+run_test "abc" "abcdef" "MATCH:abc" "match at start" "show(re.match(pattern, text))" --match
+run_test "bcd" "abcdef" "NO_MATCH" "match not at start" "show(re.match(pattern, text))" --match
+run_test "a*" "bbb" "MATCH:" "match empty at start" "show(re.match(pattern, text))" --match
+run_test "(\\w+)@(\\w+)" "user@host rest" "MATCH:user@host"$'\n'"GROUP 1:user"$'\n'"GROUP 2:host" "match with groups" "show(re.match(pattern, text))" --match
+run_test "(?<=x)a" "abc" "NO_MATCH" "match lookbehind at start" "show(re.match(pattern, text))" --match
+run_test "abc" "abc" "MATCH:abc" "fullmatch exact" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "abc" "abcd" "NO_MATCH" "fullmatch trailing text" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "a|ab" "ab" "MATCH:ab" "fullmatch backtracks into later alternative" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "(a+?)(b*)" "aaab" "MATCH:aaab"$'\n'"GROUP 1:aaa"$'\n'"GROUP 2:b" "fullmatch extends lazy quantifier" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "\\d{3}-\\d{4}" "555-1234" "MATCH:555-1234" "fullmatch validator" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "\\d{3}-\\d{4}" "555-12345" "NO_MATCH" "fullmatch validator too long" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "(?=a)\\w+" "abc" "MATCH:abc" "fullmatch with lookahead" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "(?:ab)*" "" "MATCH:" "fullmatch empty input" "show(re.fullmatch(pattern, text))" --fullmatch
+run_test "a(?=bc)" "abc" "NO_MATCH" "fullmatch lookahead past end of match" "show(re.fullmatch(pattern, text))" --fullmatch
+
+echo "=== Search Ranges ==="
+run_test "\\w+" "hello world" "MATCH:llo" "range cuts into words" "show(re.compile(pattern).search(text, 2, 8))" --pos 2 --endpos 8
+run_test "^\\w" "hello" "NO_MATCH" "caret does not match at pos" "show(re.compile(pattern).search(text, 1, 5))" --pos 1 --endpos 5
+run_test "\\w$" "hello" "MATCH:l" "dollar matches at endpos" "show(re.compile(pattern).search(text, 0, 3))" --pos 0 --endpos 3
+run_test "\\bello" "hello" "NO_MATCH" "word boundary sees text before pos" "show(re.compile(pattern).search(text, 1, 5))" --pos 1 --endpos 5
+run_test "hell\\b" "hello" "MATCH:hell" "word boundary at endpos" "show(re.compile(pattern).search(text, 0, 4))" --pos 0 --endpos 4
+run_test "(?<=h)ello" "hello" "MATCH:ello" "lookbehind sees text before pos" "show(re.compile(pattern).search(text, 1, 5))" --pos 1 --endpos 5
+run_test "hel(?=l)" "hello" "NO_MATCH" "lookahead cannot see past endpos" "show(re.compile(pattern).search(text, 0, 3))" --pos 0 --endpos 3
+run_test "(o)\\s(w)" "hello world" "MATCH:o w"$'\n'"GROUP 1:o"$'\n'"GROUP 2:w" "groups inside range" "show(re.compile(pattern).search(text, 2, 11))" --pos 2 --endpos 11
+run_test "x*" "hello" "NO_MATCH" "pos after endpos" "show(re.compile(pattern).search(text, 3, 2))" --pos 3 --endpos 2
+run_test "lo$" "hello" "MATCH:lo" "endpos past the end" "show(re.compile(pattern).search(text, 0, 99))" --pos 0 --endpos 99
+run_test "x*" "hello" "MATCH:" "pos past the end" "show(re.compile(pattern).search(text, 9, 99))" --pos 9 --endpos 99
+run_test "\\Aworld" "hello world" "NO_MATCH" "text anchor is not at pos" "show(re.compile(pattern).search(text, 6, 11))" --pos 6 --endpos 11
+run_test "a*|b" "ab" "MATCH:" "match starts at pos" "show(re.compile(pattern).match(text, 1))" --match --pos 1
+run_test "b|ab" "ab" "NO_MATCH" "match stops at endpos" "show(re.compile(pattern).match(text, 0, 1))" --match --endpos 1
+run_test "(?<=a)b" "abc" "MATCH:b" "match lookbehind sees text before pos" "show(re.compile(pattern).match(text, 1, 2))" --match --pos 1 --endpos 2
+run_test "b" "abc" "MATCH:b" "fullmatch between pos and endpos" "show(re.compile(pattern).fullmatch(text, 1, 2))" --fullmatch --pos 1 --endpos 2
+run_test "b" "abc" "NO_MATCH" "fullmatch from pos needs to reach the end" "show(re.compile(pattern).fullmatch(text, 1))" --fullmatch --pos 1
+
+echo "=== Grep ==="
+GREP_DIR=$(mktemp -d)
+printf 'foo bar\nfoobar\nBAR baz\n' > "$GREP_DIR/a.txt"
+mkdir "$GREP_DIR/sub"
+printf 'bar\nnone\n' > "$GREP_DIR/sub/b.txt"
+GREP_IN=$'foo bar\nfoobar\nBAR baz\nx\n'
+run_test "grep" "grep this" "MATCH:grep" "subcommand names are plain patterns"
+run_test "replace" "replace" "MATCH:replace" "replace as a plain pattern"
+run_test "split" "no" "NO_MATCH" "split as a plain pattern"
+run_cmd_expect "$GREP_IN" $'foo bar\nfoobar\n[exit 0]' "grep selects matching lines" --grep bar
+run_cmd_expect "$GREP_IN" $'1:foo bar\n2:foobar\n[exit 0]' "grep -n numbers lines" --grep -n bar
+run_cmd_expect "$GREP_IN" $'3\n[exit 0]' "grep -ic counts ignoring case" --grep -ic bar
+run_cmd_expect "$GREP_IN" $'BAR baz\nx\n[exit 0]' "grep -v inverts" --grep -v 'o+'
+run_cmd_expect "$GREP_IN" $'bar\n[exit 0]' "grep -w needs word edges" --grep -ow bar
+run_cmd_expect "$GREP_IN" $'foo\nfoo\n[exit 0]' "grep -o prints each match" --grep -o 'f\w\w'
+run_cmd_expect "$GREP_IN" $'foobar\n[exit 0]' "grep -x matches whole lines" --grep -x 'fo+bar'
+run_cmd_expect "$GREP_IN" $'[exit 1]' "grep without a match exits 1" --grep zzz
+run_cmd_expect "" $'regex-engine --grep: Expected \')\', got end of pattern\n[exit 2]' "grep reports bad patterns" --grep '('
+run_cmd_expect $'xb\n' $'regex-engine --grep: Unexpected character \')\' at position 1\n[exit 2]' "grep -x rejects an unbalanced pattern" --grep -x 'a)|(b'
+run_cmd_expect $'xb\n' $'regex-engine --grep: Unexpected character \')\' at position 1\n[exit 2]' "grep -w rejects an unbalanced pattern" --grep -w 'a)|(b'
+run_cmd_expect $'(())\n(()\n' $'(())\n[exit 0]' "grep -x keeps (?R) recursing into the pattern" --grep -x '\((?:[^()]|(?R))*\)'
+run_cmd_expect $'x (a(b)) y\nx(a)\n' $'(a(b))\n[exit 0]' "grep -w keeps (?R) recursing into the pattern" --grep -ow '\((?:\w|(?R))*\)'
+run_cmd_expect $'foobar foo\n' $'foo\n[exit 0]' "grep -w passes over a match inside a word" --grep -ow 'foo'
+run_cmd_expect $'x.ab\n' $'ab\n[exit 0]' "grep -w tries later starts inside a rejected match" --grep -ow '\.?ab'
+run_cmd_expect "" "$GREP_DIR/a.txt:3"$'\n'"$GREP_DIR/sub/b.txt:1"$'\n[exit 0]' "grep names files when given several" --grep -ic bar "$GREP_DIR/a.txt" "$GREP_DIR/sub/b.txt"
+run_cmd_expect "" "$GREP_DIR/a.txt"$'\n'"$GREP_DIR/sub/b.txt"$'\n[exit 0]' "grep -rl lists matching files" --grep -rl bar "$GREP_DIR"
+run_cmd_expect "" "$GREP_DIR/a.txt"$'\n[exit 0]' "grep -l names a single file" --grep -l bar "$GREP_DIR/a.txt"
+run_cmd_expect $'bar\n' $'(standard input)\n[exit 0]' "grep -l names stdin" --grep -l bar
+run_cmd_expect "" "regex-engine --grep: $GREP_DIR: Is a directory"$'\n[exit 2]' "grep needs -r for directories" --grep bar "$GREP_DIR"
+run_cmd_expect $'bar\n' $'(standard input):1:bar\n'"$GREP_DIR/sub/b.txt:1:bar"$'\n[exit 0]' "grep names stdin as -" --grep -n bar - "$GREP_DIR/sub/b.txt"
+rm -r "$GREP_DIR"
+
+echo "=== Replace and Split ==="
+run_test "(\\w+)@(\\w+)" "a@b c@d" "b at a d at c" "numbered group references" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\2 at \\1"
+run_test "(\\w+)@(\\w+)" "a@b c@d" "b at a c@d" "count limits replacements" "re.sub(pattern, args[-1], text, count=1)" --replace --count 1 {pattern} "\\2 at \\1"
+run_test "(?P<user>\\w+)@" "x@y z@w" "<x>y <z>w" "named group reference" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "<\\g<user>>"
+run_test "(a)|b" "abab" "[a][][a][]" "unset group is empty" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "[\\1]"
+run_test "a" "banana" "baanaanaa" "whole match reference" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\g<0>\\g<0>"
+run_test "x*" "abxd" "-a-b--d-" "empty matches are replaced" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "-"
+run_test "a" "ba" "b"$'\n\t'"\\\\." "escapes in the template" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\n\\t\\\\\\."
+run_test "(b)" "ab" "" "octal escapes and two-digit groups" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\101\\0\\18"
+run_test "(b)" "ab" "" "invalid group reference" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\2"
+run_test "(b)" "ab" "" "bad escape" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\q"
+run_test "(b)" "ab" "" "unknown group name" "re.sub(pattern, args[-1], text, count=0)" --replace --count 0 {pattern} "\\g<x>"
+run_cmd_expect $'one\ntwo\n' $'0ne\ntw0\n[exit 0]' "replace keeps stdin's trailing newline" --replace --all o 0
+run_cmd_expect "" $'-b-b-b\n[exit 0]' "replace with -- before a dash template" --replace --all -- a -b aaa
+run_cmd_expect "" $'a\nb\n\nc\n[exit 0]' "split on a class" --split '[,;]' 'a,b;;c'
+run_cmd_expect "" $'regex-engine --replace: Expected \')\', got end of pattern\n[exit 2]' "replace reports bad patterns" --replace 'a(' x abc
+run_cmd_expect "" $'regex-engine --split: missing PATTERN\nUsage: regex-engine --split PATTERN [INPUT]\n[exit 2]' "split without a pattern" --split
+run_cmd_expect "" $'\na\n\nb\n\n[exit 0]' "split on empty matches" --split 'x*' 'axb'
+run_cmd_expect $'1 2  3' $'1\n2\n3\n[exit 0]' "split reads stdin" --split ' +'
+
+echo "=== JSON Output ==="
+run_test "(?P<w>\\w)(x)?" "ab c" "{\"matches\":[{\"start\":0,\"end\":1,\"text\":\"a\",\"groups\":[{\"group\":1,\"name\":\"w\",\"start\":0,\"end\":1,\"text\":\"a\"},{\"group\":2,\"start\":null,\"end\":null,\"text\":null}]},{\"start\":1,\"end\":2,\"text\":\"b\",\"groups\":[{\"group\":1,\"name\":\"w\",\"start\":1,\"end\":2,\"text\":\"b\"},{\"group\":2,\"start\":null,\"end\":null,\"text\":null}]},{\"start\":3,\"end\":4,\"text\":\"c\",\"groups\":[{\"group\":1,\"name\":\"w\",\"start\":3,\"end\":4,\"text\":\"c\"},{\"group\":2,\"start\":null,\"end\":null,\"text\":null}]}]}" "named, unset and repeated matches" "finditer_json()" --format json
+run_test "(a)|(b)" "ab" "{\"matches\":[{\"start\":0,\"end\":1,\"text\":\"a\",\"groups\":[{\"group\":1,\"start\":0,\"end\":1,\"text\":\"a\"},{\"group\":2,\"start\":null,\"end\":null,\"text\":null}]},{\"start\":1,\"end\":2,\"text\":\"b\",\"groups\":[{\"group\":1,\"start\":null,\"end\":null,\"text\":null},{\"group\":2,\"start\":1,\"end\":2,\"text\":\"b\"}]}]}" "alternate groups are null" "finditer_json()" --format json
+run_test "x*" "axb" "{\"matches\":[{\"start\":0,\"end\":0,\"text\":\"\",\"groups\":[]},{\"start\":1,\"end\":2,\"text\":\"x\",\"groups\":[]},{\"start\":2,\"end\":2,\"text\":\"\",\"groups\":[]},{\"start\":3,\"end\":3,\"text\":\"\",\"groups\":[]}]}" "empty matches" "finditer_json()" --format json
+run_test "(a*)b" $'a"\\\nb' "{\"matches\":[{\"start\":4,\"end\":5,\"text\":\"b\",\"groups\":[{\"group\":1,\"start\":4,\"end\":4,\"text\":\"\"}]}]}" "quotes, backslashes and newlines are escaped" "finditer_json()" --format json
+run_test "é(.)" "café!" "{\"matches\":[{\"start\":3,\"end\":5,\"text\":\"é!\",\"groups\":[{\"group\":1,\"start\":4,\"end\":5,\"text\":\"!\"}]}]}" "offsets are in chars" "finditer_json()" --format json
+run_test "z" "abc" "{\"matches\":[]}" "no matches" "finditer_json()" --format json
+run_cmd_expect "" $'{"matches":[{"start":0,"end":2,"text":"aa","groups":[]}]}\n[exit 0]' "--match reports at most one match" --format json --match 'a*' 'aaba'
+run_cmd_expect "" $'{"matches":[{"start":1,"end":2,"text":"a","groups":[]},{"start":2,"end":3,"text":"a","groups":[]}]}\n[exit 0]' "json honors pos and endpos" --format json --pos 1 --endpos 3 a aaaa
+run_cmd_expect "" $'{"error":{"message":"Expected \')\', got end of pattern","start":1,"end":4}}\n[exit 0]' "unterminated group error spans the group" --format json 'a(bc' x
+run_cmd_expect "" $'{"error":{"message":"min repeat greater than max repeat","start":0,"end":6}}\n[exit 0]' "bad repeat error spans the quantifier" --format json 'a{3,2}' x
+run_cmd_expect "" $'{"error":{"message":"Unexpected character \')\' at position 1","start":1,"end":2}}\n[exit 0]' "unbalanced paren error spans the paren" --format json 'a)' x
+
+echo "=== Unset Groups ==="
+run_test "(a)|(b)" "b" "MATCH:b"$'\n'"GROUP 1:<unset>"$'\n'"GROUP 2:b" "group in the other branch is unset" "show(re.search(pattern, text), '<unset>')" --show-unset
+run_test "(a*)b" "b" "MATCH:b"$'\n'"GROUP 1:" "empty group is not unset" "show(re.search(pattern, text), '<unset>')" --show-unset
+run_test "(x)?y" "y" "MATCH:y"$'\n'"GROUP 1:<unset>" "skipped optional group is unset" "show(re.search(pattern, text), '<unset>')" --show-unset
+run_test "(?:(a)|b)+" "ab" "MATCH:ab"$'\n'"GROUP 1:a" "group from an earlier iteration stays set" "show(re.search(pattern, text), '<unset>')" --show-unset
+run_test "(a)?(b)?()" "" "MATCH:"$'\n'"GROUP 1:<unset>"$'\n'"GROUP 2:<unset>"$'\n'"GROUP 3:" "mix of unset and empty" "show(re.search(pattern, text), '<unset>')" --show-unset
+run_test "(?P<n>z)|q" "q" "MATCH:q"$'\n'"GROUP 1:<unset>" "named group unset" "show(re.search(pattern, text), '<unset>')" --show-unset
+
+# === DEBUG DUMPS ===
+echo "=== Debug Dumps ==="
+run_cmd_expect "" $'Concat\n  Quantifier + greedy\n    Group 1\n      Alternation\n        Literal \'a\'\n        Literal \'b\'\n  Literal \'c\'\nMATCH:abc\nGROUP 1:b\n[exit 0]' "--dump-ast prints the tree" --dump-ast '(a|b)+c' xabc
+run_cmd_expect "" $'first_set: [a]\nanchored_start: true\n0  assert ^\n1  char \'a\'\n2  char \'a\'\n3  match\nNO_MATCH\n[exit 0]' "--dump-program lists instructions" --dump-program '^a{2}' x
+run_cmd_expect "" $'first_set: [ab]\nanchored_start: false\n0  save 2\n1  class [ab]\n2  save 3\n3  split 0, 4\n4  char \'c\'\n5  match\nMATCH:abc\nGROUP 1:b\n[exit 0]' "--dump-program shows jump targets" --dump-program '(a|b)+c' xabc
+run_cmd_expect "" $'attempt at 0\n     1  pc   0  pos   0  str "ab"\n     2  pc   1  pos   2  match\n2 steps\nMATCH:ab\n[exit 0]' "--trace logs each step" --trace ab ab
+run_cmd_expect "" $'attempt at 0\n     1  pc   0  pos   0  split 1, 4\n     2  pc   1  pos   0  char \'a\'\n     3  pc   2  pos   1  class \\d\n        backtrack from pc 1 at pos 0\n     4  pc   4  pos   0  any\n     5  pc   5  pos   1  match\n5 steps\nMATCH:a\n[exit 0]' "--trace logs backtracks" --trace 'a\d|.' ax
+run_cmd_expect "" $'--trace only applies to a plain search, not --match, --fullmatch or --format json\n[exit 1]' "--trace rejects --format json" --trace --format json a aa
+run_cmd_expect "" $'--trace only applies to a plain search, not --match, --fullmatch or --format json\n[exit 1]' "--trace rejects --match" --trace --match a aa
+
 # === REPORT ===
 echo ""
 echo "================================"
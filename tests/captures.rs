@@ -0,0 +1,59 @@
+//! `Captures` and `MatchResult::group`: a group that didn't take part is
+//! `None`, one that matched the empty string is an empty `Match`.
+
+use regex_engine::{Match, Regex};
+
+fn m(start: usize, end: usize, text: &str) -> Match {
+    Match { start, end, text: text.to_string() }
+}
+
+#[test]
+fn unset_and_empty_groups_differ() {
+    let regex = Regex::new("(a)|(b)()").unwrap();
+    let caps = regex.captures("xb").unwrap();
+    assert_eq!(caps.get(0), Some(&m(1, 2, "b")));
+    assert_eq!(caps.get(1), None);
+    assert_eq!(caps.get(2), Some(&m(1, 2, "b")));
+    assert_eq!(caps.get(3), Some(&m(2, 2, "")));
+    assert_eq!(caps.get(4), None);
+    let groups: Vec<Option<&str>> = caps.iter().map(|g| g.map(|g| g.text.as_str())).collect();
+    assert_eq!(groups, [Some("b"), None, Some("b"), Some("")]);
+}
+
+#[test]
+fn match_result_group_agrees() {
+    let regex = Regex::new("(x)?(y*)z").unwrap();
+    let result = regex.search("az").unwrap();
+    assert_eq!(result.group(0), Some((1, 2)));
+    assert_eq!(result.group(1), None);
+    assert_eq!(result.group(2), Some((1, 1)));
+    assert_eq!(result.group(3), None);
+}
+
+#[test]
+fn named_groups() {
+    let regex = Regex::new(r"(?P<user>\w+)@(?P<host>\w+)?").unwrap();
+    let caps = regex.captures("me@").unwrap();
+    assert_eq!(caps.name("user"), Some(&m(0, 2, "me")));
+    assert_eq!(caps.name("host"), None);
+    assert_eq!(caps.name("nope"), None);
+}
+
+#[test]
+fn offsets_are_chars() {
+    let regex = Regex::new("é(.)").unwrap();
+    let caps = regex.captures("café!").unwrap();
+    assert_eq!(caps.get(1), Some(&m(4, 5, "!")));
+}
+
+#[test]
+fn captures_iter_reports_each_match() {
+    let regex = Regex::new(r"(\d)|([a-z])").unwrap();
+    let all: Vec<(Option<String>, Option<String>)> = regex
+        .captures_iter("1a2")
+        .map(|caps| (caps.get(1).map(|g| g.text.clone()), caps.get(2).map(|g| g.text.clone())))
+        .collect();
+    let text = |s: &str| Some(s.to_string());
+    assert_eq!(all, [(text("1"), None), (None, text("a")), (text("2"), None)]);
+    assert!(regex.captures("!").is_none());
+}
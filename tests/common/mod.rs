@@ -0,0 +1,135 @@
+//! Random patterns and inputs for the differential tests, from a fixed seed
+//! so failures reproduce.
+
+#![allow(dead_code)]
+
+/// A xorshift generator; good enough for picking pattern pieces.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// How many inputs each generated case is checked on.
+const INPUTS_PER_CASE: usize = 6;
+
+/// Longest generated input, in characters.
+const MAX_INPUT_LEN: usize = 12;
+
+/// Run `check` on `cases` random cases from `seed`: a set of up to
+/// `max_patterns` patterns (extended in half the cases; those that don't
+/// compile are dropped), each time with a new input. `check` gets the
+/// generator for any further random choices.
+pub fn for_each_set(seed: u64, cases: usize, max_patterns: usize, mut check: impl FnMut(&mut Rng, &[String], &str)) {
+    let mut rng = Rng::new(seed);
+    for _ in 0..cases {
+        let extended = rng.below(2) == 0;
+        let patterns: Vec<String> = (0..1 + rng.below(max_patterns))
+            .map(|_| pattern(&mut rng, extended))
+            .filter(|p| regex_engine::Regex::new(p).is_ok())
+            .collect();
+        for _ in 0..INPUTS_PER_CASE {
+            let input = input(&mut rng, MAX_INPUT_LEN);
+            check(&mut rng, &patterns, &input);
+        }
+    }
+}
+
+/// `for_each_set` with a single pattern per case.
+pub fn for_each_case(seed: u64, cases: usize, mut check: impl FnMut(&mut Rng, &str, &str)) {
+    for_each_set(seed, cases, 1, |rng, patterns, input| {
+        if let [pattern] = patterns {
+            check(rng, pattern, input);
+        }
+    });
+}
+
+/// A pattern of literals, classes, `.`, `\d`, anchors, groups, alternation
+/// and quantifiers over the characters of `input`. With `extended`, also
+/// backreferences, lookarounds, atomic groups and counted loops, which the
+/// Pike VM doesn't run. Only bodies that can't match empty are repeated:
+/// loops over empty matches can hang (see Known Issues in PROGRESS.md).
+pub fn pattern(rng: &mut Rng, extended: bool) -> String {
+    let mut groups = 0;
+    sequence(rng, extended, 0, &mut groups).0
+}
+
+/// Text of up to `max_len` characters from `ab1 é`, so patterns match often
+/// and some characters take more than one UTF-8 byte.
+pub fn input(rng: &mut Rng, max_len: usize) -> String {
+    let len = rng.below(max_len + 1);
+    (0..len).map(|_| *rng.pick(&['a', 'b', 'a', 'b', '1', ' ', 'é'])).collect()
+}
+
+/// A pattern fragment and whether it can match the empty string.
+fn sequence(rng: &mut Rng, extended: bool, depth: usize, groups: &mut usize) -> (String, bool) {
+    let mut text = String::new();
+    let mut empty = true;
+    for _ in 0..1 + rng.below(3) {
+        let (atom, atom_empty) = quantified(rng, extended, depth, groups);
+        text.push_str(&atom);
+        empty &= atom_empty;
+    }
+    (text, empty)
+}
+
+fn quantified(rng: &mut Rng, extended: bool, depth: usize, groups: &mut usize) -> (String, bool) {
+    let (atom, empty) = atom(rng, extended, depth, groups);
+    if empty || rng.below(3) > 0 {
+        return (atom, empty);
+    }
+    let counted = if extended { 6 } else { 4 };
+    match rng.below(counted) {
+        0 => (atom + *rng.pick(&["*", "*?"]), true),
+        1 => (atom + *rng.pick(&["+", "+?"]), false),
+        2 => (atom + *rng.pick(&["?", "??"]), true),
+        3 => (atom + "{1,2}", false),
+        _ => (atom + *rng.pick(&["{2,40}", "{0,30}"]), true),
+    }
+}
+
+fn atom(rng: &mut Rng, extended: bool, depth: usize, groups: &mut usize) -> (String, bool) {
+    let kinds = if depth >= 2 { 4 } else if extended { 9 } else { 6 };
+    match rng.below(kinds) {
+        0 | 1 => (rng.pick(&["a", "b", "ab", "é", " "]).to_string(), false),
+        2 => (rng.pick(&["[ab]", "[^a]", ".", r"\d", r"\w", r"\s"]).to_string(), false),
+        3 => (rng.pick(&["^", "$", r"\b", r"\B"]).to_string(), true),
+        4 | 5 => {
+            let (mut body, mut empty) = sequence(rng, extended, depth + 1, groups);
+            if rng.below(2) == 0 {
+                let (other, other_empty) = sequence(rng, extended, depth + 1, groups);
+                body = format!("{}|{}", body, other);
+                empty |= other_empty;
+            }
+            if rng.below(2) == 0 {
+                *groups += 1;
+                (format!("({})", body), empty)
+            } else {
+                (format!("(?:{})", body), empty)
+            }
+        }
+        6 if *groups > 0 => (format!("\\{}", 1 + rng.below(*groups)), true),
+        6 | 7 => {
+            let (body, _) = sequence(rng, extended, depth + 1, groups);
+            (format!("({}{})", rng.pick(&["?=", "?!", "?<="]), body), true)
+        }
+        _ => {
+            let (body, empty) = sequence(rng, extended, depth + 1, groups);
+            (format!("(?>{})", body), empty)
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! `Regex::find_iter`: `\G` chaining and empty-match advancement, with
+//! expectations from Python's `re.finditer`.
+
+use regex_engine::Regex;
+
+fn spans(pattern: &str, input: &str) -> Vec<(usize, usize)> {
+    Regex::new(pattern).unwrap().find_iter(input).map(|m| (m.start, m.end)).collect()
+}
+
+#[test]
+fn g_tokenizes_contiguous_matches() {
+    assert_eq!(spans(r"\G\w+,?", "ab,cd,ef"), [(0, 3), (3, 6), (6, 8)]);
+    assert_eq!(spans(r"\G(?:\d+|[+*])", "12+3*45"), [(0, 2), (2, 3), (3, 4), (4, 5), (5, 7)]);
+}
+
+#[test]
+fn g_stops_at_the_first_gap() {
+    assert_eq!(spans(r"\G\w+,?", "ab,cd ef"), [(0, 3), (3, 5)]);
+    assert_eq!(spans(r"\Ga", "aaba"), [(0, 1), (1, 2)]);
+    assert_eq!(spans(r"\Ga", "ba"), []);
+}
+
+#[test]
+fn g_after_an_empty_match() {
+    // The empty match at 0 is followed by a non-empty one at the same place.
+    assert_eq!(spans(r"\Ga*", "aab"), [(0, 2), (2, 2)]);
+    assert_eq!(spans(r"\G", "ab"), [(0, 0)]);
+}
+
+#[test]
+fn empty_matches_advance_one_char() {
+    assert_eq!(spans("a*", "baaa c"), [(0, 0), (1, 4), (4, 4), (5, 5), (6, 6)]);
+    assert_eq!(spans("(?=a)", "aa"), [(0, 0), (1, 1)]);
+    assert_eq!(spans(r"\b", "ab cd"), [(0, 0), (2, 2), (3, 3), (5, 5)]);
+    assert_eq!(spans("x*", ""), [(0, 0)]);
+}
+
+#[test]
+fn non_empty_match_allowed_where_an_empty_one_ended() {
+    assert_eq!(spans("|a", "a"), [(0, 0), (0, 1), (1, 1)]);
+}
+
+#[test]
+fn matches_are_char_offsets() {
+    assert_eq!(spans("é+", "aéé€é"), [(1, 3), (4, 5)]);
+}
@@ -0,0 +1,137 @@
+//! `Lexer` rule priority, token spans and error tokens, and a comparison
+//! with a naive lexer running each rule through `Regex::match_at`.
+
+mod common;
+
+use regex_engine::lexer::{Priority, Token};
+use regex_engine::{Lexer, Regex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Keyword,
+    Number,
+    Space,
+    Op,
+}
+
+fn lex<K: Clone>(rules: Vec<(K, &str)>, priority: Priority, input: &str) -> Vec<(Option<K>, usize, usize)> {
+    let lexer = Lexer::new(rules, priority).unwrap();
+    lexer.tokens(input).map(|t: Token<K>| (t.kind, t.start, t.end)).collect()
+}
+
+fn keyword_rules() -> Vec<(Kind, &'static str)> {
+    vec![(Kind::Keyword, "if|in"), (Kind::Ident, r"[a-z]\w*"), (Kind::Number, r"\d+"), (Kind::Space, r"\s+")]
+}
+
+#[test]
+fn longest_match_wins_ties_go_to_the_earlier_rule() {
+    let tokens = lex(keyword_rules(), Priority::Longest, "if info 42");
+    assert_eq!(
+        tokens,
+        [
+            (Some(Kind::Keyword), 0, 2),
+            (Some(Kind::Space), 2, 3),
+            (Some(Kind::Ident), 3, 7),
+            (Some(Kind::Space), 7, 8),
+            (Some(Kind::Number), 8, 10),
+        ]
+    );
+}
+
+#[test]
+fn first_matching_rule_wins_however_short() {
+    let tokens = lex(keyword_rules(), Priority::First, "info");
+    assert_eq!(tokens, [(Some(Kind::Keyword), 0, 2), (Some(Kind::Ident), 2, 4)]);
+}
+
+#[test]
+fn a_rule_contributes_its_leftmost_first_match() {
+    // `a|ab` gives "a" on "ab" even though it could match "ab".
+    let tokens = lex(vec![(Kind::Ident, "a|ab"), (Kind::Op, "b")], Priority::Longest, "ab");
+    assert_eq!(tokens, [(Some(Kind::Ident), 0, 1), (Some(Kind::Op), 1, 2)]);
+}
+
+#[test]
+fn tokens_are_never_empty() {
+    let tokens = lex(vec![(Kind::Ident, "a*?"), (Kind::Space, "x?")], Priority::First, "aax");
+    assert_eq!(tokens, [(Some(Kind::Ident), 0, 1), (Some(Kind::Ident), 1, 2), (Some(Kind::Space), 2, 3)]);
+}
+
+#[test]
+fn unmatched_input_is_one_error_token() {
+    let tokens = lex(keyword_rules(), Priority::Longest, "a+-*b");
+    assert_eq!(tokens, [(Some(Kind::Ident), 0, 1), (None, 1, 4), (Some(Kind::Ident), 4, 5)]);
+    assert_eq!(lex(keyword_rules(), Priority::Longest, "%%"), [(None, 0, 2)]);
+}
+
+#[test]
+fn spans_are_char_offsets() {
+    let tokens = lex(vec![(Kind::Ident, r"\w+"), (Kind::Op, "é+")], Priority::Longest, "aééb");
+    assert_eq!(tokens, [(Some(Kind::Ident), 0, 1), (Some(Kind::Op), 1, 3), (Some(Kind::Ident), 3, 4)]);
+}
+
+#[test]
+fn assertions_see_the_whole_input() {
+    // `\G` is the token start; `^` only the start of the input; lookbehind
+    // and `\b` read the previous token's text.
+    let rules = vec![(Kind::Keyword, r"^\w+"), (Kind::Op, r"(?<=a)\G\d"), (Kind::Ident, r"\b\w"), (Kind::Number, r"\d")];
+    let tokens = lex(rules, Priority::First, "ab1 a2");
+    assert_eq!(
+        tokens,
+        [(Some(Kind::Keyword), 0, 3), (None, 3, 4), (Some(Kind::Ident), 4, 5), (Some(Kind::Op), 5, 6)]
+    );
+}
+
+#[test]
+fn errors_name_the_rule() {
+    let err = Lexer::new(vec![(Kind::Ident, "a"), (Kind::Op, "(b")], Priority::First).err().unwrap();
+    assert!(err.starts_with("rule 1: "), "{}", err);
+}
+
+/// The tokens `Lexer` should produce, found by trying every rule at every
+/// position with `Regex::match_at`. `(?!\G)` after a rule rejects empty
+/// matches the way the lexer does, by backtracking into the rule.
+fn naive(rules: &[String], priority: Priority, input: &str) -> Vec<(Option<usize>, usize, usize)> {
+    let regexes: Vec<Regex> = rules.iter().map(|r| Regex::new(&format!(r"(?:{})(?!\G)", r)).unwrap()).collect();
+    let token_at = |pos: usize| {
+        let mut best: Option<(usize, usize)> = None;
+        for (id, regex) in regexes.iter().enumerate() {
+            let Some(m) = regex.match_at(input, pos) else { continue };
+            if priority == Priority::First {
+                return Some((id, m.end));
+            }
+            if best.is_none_or(|(_, end)| m.end > end) {
+                best = Some((id, m.end));
+            }
+        }
+        best
+    };
+    let len = input.chars().count();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < len {
+        match token_at(pos) {
+            Some((id, end)) => {
+                tokens.push((Some(id), pos, end));
+                pos = end;
+            }
+            None => {
+                let end = (pos + 1..len).find(|&p| token_at(p).is_some()).unwrap_or(len);
+                tokens.push((None, pos, end));
+                pos = end;
+            }
+        }
+    }
+    tokens
+}
+
+#[test]
+fn agrees_with_a_naive_lexer_on_random_rules() {
+    common::for_each_set(42, 300, 5, |rng, rules, input| {
+        let priority = if rng.below(2) == 0 { Priority::Longest } else { Priority::First };
+        let ids: Vec<(usize, &str)> = rules.iter().map(String::as_str).enumerate().collect();
+        let actual = lex(ids, priority, input);
+        assert_eq!(actual, naive(rules, priority, input), "rules {:?}, {:?} on {:?}", rules, priority, input);
+    });
+}
@@ -0,0 +1,18 @@
+//! The compiled program size limit: nested repetitions stay small, only
+//! genuinely huge patterns are rejected.
+
+use regex_engine::Regex;
+
+#[test]
+fn nested_small_counts_do_not_multiply() {
+    let pattern = "(((((((a{8}){8}){8}){8}){8}){8}){8})";
+    assert!(Regex::new(pattern).is_ok());
+    let regex = Regex::new(r"(?:(?:(?:(?:(?:(?:(?:\w{1,8}){1,8}){1,8}){1,8}){1,8}){1,8}){1,8})").unwrap();
+    assert_eq!(regex.search("hello world").map(|m| (m.start, m.end)), Some((0, 5)));
+}
+
+#[test]
+fn huge_patterns_are_rejected() {
+    let err = Regex::new(&".".repeat(1_000_001)).err().unwrap();
+    assert_eq!(err, "pattern too large: compiled program exceeds 1000000 instructions");
+}
@@ -0,0 +1,75 @@
+//! `RegexSet` against each pattern searched on its own with `Regex::search`.
+
+mod common;
+
+use regex_engine::{Regex, RegexSet};
+
+/// Check `matches` and `find_each` against per-pattern `Regex::search`.
+fn check(patterns: &[String], input: &str) {
+    let set = RegexSet::new(patterns).unwrap();
+    let expected: Vec<Option<(usize, usize)>> = patterns
+        .iter()
+        .map(|p| Regex::new(p).unwrap().search(input).map(|m| (m.start, m.end)))
+        .collect();
+    let matching: Vec<usize> = (0..patterns.len()).filter(|&i| expected[i].is_some()).collect();
+    assert_eq!(set.find_each(input), expected, "find_each, patterns {:?} on {:?}", patterns, input);
+    assert_eq!(set.matches(input), matching, "matches, patterns {:?} on {:?}", patterns, input);
+    assert_eq!(set.is_match(input), !matching.is_empty());
+}
+
+fn strings(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn reports_each_pattern() {
+    let set = RegexSet::new(["foo", r"\d+", "x$", "bar"]).unwrap();
+    assert_eq!(set.len(), 4);
+    assert_eq!(set.matches("foo 42 bar"), [0, 1, 3]);
+    assert_eq!(set.find_each("foo 42 bar"), [Some((0, 3)), Some((4, 6)), None, Some((7, 10))]);
+    assert!(!set.is_match("nothing"));
+}
+
+#[test]
+fn spans_are_each_patterns_leftmost_match() {
+    let patterns = strings(&["a+", "a+?", "b|ab", r"\bb\w*", "(?:a|ab)(?:c|bcd)"]);
+    for input in ["", "aab", "abcd", "cab bab", "xaaab"] {
+        check(&patterns, input);
+    }
+}
+
+#[test]
+fn backtracking_only_patterns_fall_back() {
+    let patterns = strings(&[r"(a)\1", "a(?=b)", "(?>a+)b", "a{2,40}", r"(?<=a)b", "b+"]);
+    for input in ["aab", "ab", "aaaab", "b", "ba"] {
+        check(&patterns, input);
+    }
+}
+
+#[test]
+fn literal_filter_skips_only_patterns_that_cannot_match() {
+    // Required literals, prefix sets and suffix sets, next to unfiltered ones.
+    let patterns = strings(&["needle", "(?:cat|dog)s?", r"\w+(?:ing|ed)", "[xy]", r"(?i:NeEdLe)"]);
+    for input in ["a needle", "dogs and cats", "jumped", "y", "NEEDLE", "none"] {
+        check(&patterns, input);
+    }
+}
+
+#[test]
+fn errors_name_the_pattern() {
+    let err = RegexSet::new(["a", "b)"]).err().unwrap();
+    assert!(err.starts_with("pattern 1: "), "{}", err);
+}
+
+#[test]
+fn empty_set() {
+    let set = RegexSet::new(Vec::<String>::new()).unwrap();
+    assert!(set.is_empty());
+    assert_eq!(set.matches("abc"), Vec::<usize>::new());
+    assert!(set.find_each("abc").is_empty());
+}
+
+#[test]
+fn agrees_with_regex_on_random_patterns() {
+    common::for_each_set(41, 300, 6, |_, patterns, input| check(patterns, input));
+}
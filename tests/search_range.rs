@@ -0,0 +1,96 @@
+//! `Regex::search_range` with context and sliced edges.
+
+mod common;
+
+use regex_engine::Regex;
+use regex_engine::vm::{Edge, RangeOptions};
+
+fn find(pattern: &str, input: &str, start: usize, end: usize, options: RangeOptions) -> Option<(usize, usize)> {
+    Regex::new(pattern).unwrap().search_range(input, start, end, options).map(|m| (m.start, m.end))
+}
+
+const CONTEXT: RangeOptions = RangeOptions::CONTEXT;
+const SLICE: RangeOptions = RangeOptions::SLICE;
+
+#[test]
+fn context_end_is_visible_to_lookahead() {
+    assert_eq!(find("a(?=b)", "xab", 0, 2, CONTEXT), Some((1, 2)));
+    assert_eq!(find("a(?!b)", "xab", 0, 2, CONTEXT), None);
+    assert_eq!(find("a(?=b)", "xab", 0, 2, SLICE), None);
+    assert_eq!(find("a(?!b)", "xab", 0, 2, SLICE), Some((1, 2)));
+}
+
+#[test]
+fn context_end_decides_word_boundaries() {
+    assert_eq!(find(r"a\b", "ab", 0, 1, CONTEXT), None);
+    assert_eq!(find(r"a\B", "ab", 0, 1, CONTEXT), Some((0, 1)));
+    assert_eq!(find(r"a\b", "ab", 0, 1, SLICE), Some((0, 1)));
+    assert_eq!(find(r"a\b", "a b", 0, 1, CONTEXT), Some((0, 1)));
+}
+
+#[test]
+fn context_end_is_not_the_end_of_input() {
+    assert_eq!(find("a$", "ab", 0, 1, CONTEXT), None);
+    assert_eq!(find("a$", "ab", 0, 1, SLICE), Some((0, 1)));
+    // Matches stay inside the range either way.
+    assert_eq!(find("ab", "ab", 0, 1, CONTEXT), None);
+    assert_eq!(find("a.*", "aaaa", 0, 2, CONTEXT), Some((0, 2)));
+}
+
+#[test]
+fn slice_start_is_the_start_of_input() {
+    assert_eq!(find("^b", "ab", 1, 2, SLICE), Some((1, 2)));
+    assert_eq!(find("^b", "ab", 1, 2, CONTEXT), None);
+    assert_eq!(find(r"\Ab", "ab", 1, 2, SLICE), Some((1, 2)));
+    assert_eq!(find(r"\bb", "ab", 1, 2, SLICE), Some((1, 2)));
+    assert_eq!(find(r"\bb", "ab", 1, 2, CONTEXT), None);
+}
+
+#[test]
+fn slice_start_hides_text_from_lookbehind() {
+    assert_eq!(find("(?<=a)b", "ab", 1, 2, SLICE), None);
+    assert_eq!(find("(?<!a)b", "ab", 1, 2, SLICE), Some((1, 2)));
+    assert_eq!(find("(?<=a)b", "ab", 1, 2, CONTEXT), Some((1, 2)));
+    assert_eq!(find("(?<=a)b", "ab", 1, 2, RangeOptions::PYTHON), Some((1, 2)));
+}
+
+#[test]
+fn g_matches_at_the_range_start() {
+    assert_eq!(find(r"\Gb", "abab", 1, 4, CONTEXT), Some((1, 2)));
+    assert_eq!(find(r"\Gb", "abab", 2, 4, SLICE), None);
+}
+
+#[test]
+fn edges_mix() {
+    let options = RangeOptions { start: Edge::Slice, end: Edge::Context };
+    assert_eq!(find(r"^b(?=c)", "abc", 1, 2, options), Some((1, 2)));
+    assert_eq!(find(r"^b$", "abc", 1, 2, options), None);
+}
+
+#[test]
+fn group_offsets_are_into_the_whole_input() {
+    let regex = Regex::new("(b)(c)?").unwrap();
+    let m = regex.search_range("abcb", 1, 2, SLICE).unwrap();
+    assert_eq!((m.start, m.end, m.group(1), m.group(2)), (1, 2, Some((1, 2)), None));
+}
+
+#[test]
+fn bad_ranges_find_nothing() {
+    assert_eq!(find("", "ab", 2, 1, CONTEXT), None);
+    assert_eq!(find("", "ab", 0, 3, SLICE), None);
+    assert_eq!(find("", "ab", 2, 2, SLICE), Some((2, 2)));
+}
+
+#[test]
+fn slice_is_searching_the_substring() {
+    common::for_each_case(44, 150, |rng, pattern, input| {
+        let regex = Regex::new(pattern).unwrap();
+        let len = input.chars().count();
+        let start = rng.below(len + 1);
+        let end = start + rng.below(len - start + 1);
+        let sub: String = input.chars().skip(start).take(end - start).collect();
+        let expected = regex.search(&sub).map(|m| (m.start + start, m.end + start));
+        let actual = regex.search_range(input, start, end, SLICE).map(|m| (m.start, m.end));
+        assert_eq!(actual, expected, "{:?} on {:?}[{}..{}]", pattern, input, start, end);
+    });
+}
@@ -0,0 +1,126 @@
+//! Streaming search: matches settle as chunks arrive and equal `find_iter`'s
+//! on the whole input, whatever the chunking.
+
+mod common;
+
+use regex_engine::Regex;
+use std::io::{self, Read};
+
+fn spans(matches: Vec<regex_engine::vm::MatchResult>) -> Vec<(usize, usize)> {
+    matches.into_iter().map(|m| (m.start, m.end)).collect()
+}
+
+/// Feed `chunks` one at a time and collect the matches each one settles.
+fn feed_all(pattern: &str, chunks: &[&str]) -> Vec<Vec<(usize, usize)>> {
+    let regex = Regex::new(pattern).unwrap();
+    let mut searcher = regex.stream();
+    let mut settled: Vec<Vec<(usize, usize)>> = chunks.iter().map(|chunk| spans(searcher.feed(chunk))).collect();
+    settled.push(spans(searcher.finish()));
+    settled
+}
+
+fn find_iter(regex: &Regex, input: &str) -> Vec<(usize, usize)> {
+    regex.find_iter(input).map(|m| (m.start, m.end)).collect()
+}
+
+/// A reader returning at most `size` bytes per read.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    size: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.size.min(buf.len()).min(self.bytes.len());
+        buf[..n].copy_from_slice(&self.bytes[..n]);
+        self.bytes = &self.bytes[n..];
+        Ok(n)
+    }
+}
+
+fn read_all(pattern: &str, bytes: &[u8], size: usize) -> io::Result<Vec<(usize, usize)>> {
+    let mut found = Vec::new();
+    Regex::new(pattern).unwrap().stream_matches(Trickle { bytes, size }, |m| found.push((m.start, m.end)))?;
+    Ok(found)
+}
+
+#[test]
+fn match_waits_until_it_cannot_grow() {
+    assert_eq!(feed_all("a+", &["xaa", "a", "ab", "a"]), [vec![], vec![], vec![(1, 5)], vec![], vec![(6, 7)]]);
+}
+
+#[test]
+fn end_assertions_wait_for_more_input() {
+    assert_eq!(feed_all(r"a\b", &["a", " a", "a"]), [vec![], vec![(0, 1)], vec![], vec![(3, 4)]]);
+    assert_eq!(feed_all("a$", &["a", "a"]), [vec![], vec![], vec![(1, 2)]]);
+}
+
+#[test]
+fn matches_span_chunks_with_absolute_offsets() {
+    assert_eq!(feed_all("abc", &["xa", "b", "cxab", "c"]), [vec![], vec![], vec![(1, 4)], vec![(5, 8)], vec![]]);
+}
+
+#[test]
+fn empty_matches_and_g() {
+    assert_eq!(feed_all("x*", &["a", "xx", "b"]), [vec![(0, 0)], vec![], vec![(1, 3), (3, 3)], vec![(4, 4)]]);
+    assert_eq!(feed_all(r"\Ga", &["aa", "ba"]), [vec![(0, 1), (1, 2)], vec![], vec![]]);
+}
+
+#[test]
+fn nothing_fed() {
+    assert_eq!(feed_all("a", &[]), [vec![]]);
+    assert_eq!(feed_all("", &[]), [vec![(0, 0)]]);
+}
+
+#[test]
+fn reader_splits_characters_across_reads() {
+    let text = "é€a😀b é";
+    let regex = Regex::new(r"\S+").unwrap();
+    for size in 1..=5 {
+        assert_eq!(read_all(r"\S+", text.as_bytes(), size).unwrap(), find_iter(&regex, text), "reads of {}", size);
+    }
+}
+
+#[test]
+fn reader_rejects_bad_utf8() {
+    let err = read_all("a", b"ab\xffa", 2).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = read_all("a", "aé".as_bytes().split_last().unwrap().1, 1).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn trimmed_buffer_still_serves_lookbehind_and_word_boundaries() {
+    // Long enough that the buffer is trimmed many times over.
+    let text = "ab c1 ".repeat(300);
+    for pattern in [r"(?<=ab )c", r"(?<!b)\bc\d", r"(?<=a)b\b", r"^ab", r"\d\s(?=a)"] {
+        let regex = Regex::new(pattern).unwrap();
+        let mut searcher = regex.stream();
+        let mut found = Vec::new();
+        for c in text.chars() {
+            found.extend(spans(searcher.feed(&c.to_string())));
+        }
+        found.extend(spans(searcher.finish()));
+        assert_eq!(found, find_iter(&regex, &text), "{}", pattern);
+    }
+}
+
+#[test]
+fn agrees_with_find_iter_for_any_chunking() {
+    common::for_each_case(45, 150, |rng, pattern, input| {
+        let regex = Regex::new(pattern).unwrap();
+        let chars: Vec<char> = input.chars().collect();
+        let mut searcher = regex.stream();
+        let mut found = Vec::new();
+        let mut pos = 0;
+        while pos < chars.len() {
+            let end = (pos + 1 + rng.below(3)).min(chars.len());
+            found.extend(spans(searcher.feed(&chars[pos..end].iter().collect::<String>())));
+            pos = end;
+        }
+        found.extend(spans(searcher.finish()));
+        assert_eq!(found, find_iter(&regex, input), "{:?} on {:?}", pattern, input);
+        let size = 1 + rng.below(4);
+        assert_eq!(read_all(pattern, input.as_bytes(), size).unwrap(), found, "{:?} read {} bytes at a time", pattern, size);
+    });
+}